@@ -4,7 +4,8 @@ use csgo_gsi::{GSIConfigBuilder, GSIServer, Subscription};
 async fn main() {
     let config = GSIConfigBuilder::new("csgo-gsi Example")
         .subscribe_multiple(Subscription::UNRESTRICTED)
-        .build();
+        .try_build()
+        .expect("config wasn't valid");
 
     let mut server = GSIServer::new(config, 31337);
     server.add_listener(|update| println!("Got an update {:#?}", update));
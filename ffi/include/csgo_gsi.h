@@ -0,0 +1,149 @@
+/*
+ * C API for the csgo-gsi Rust library, built as a shared library by the csgo-gsi-ffi crate:
+ *
+ *     cargo build --release -p csgo-gsi-ffi
+ *
+ * then link against target/release/libcsgo_gsi_ffi.so (or csgo_gsi_ffi.dll, or
+ * libcsgo_gsi_ffi.dylib).
+ *
+ * functions that can fail return 0 (or a non-null pointer) on success, and -1 (or null) on
+ * failure, when csgo_gsi_last_error() says why. passing a null builder or server is a failure,
+ * and functions that can't fail just do nothing.
+ */
+
+#ifndef CSGO_GSI_H
+#define CSGO_GSI_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* which pieces of information to subscribe to, combined with | */
+#define CSGO_GSI_SUBSCRIBE_MAP_ROUND_WINS          (1u << 0)
+#define CSGO_GSI_SUBSCRIBE_MAP                     (1u << 1)
+#define CSGO_GSI_SUBSCRIBE_PLAYER_ID               (1u << 2)
+#define CSGO_GSI_SUBSCRIBE_PLAYER_MATCH_STATS      (1u << 3)
+#define CSGO_GSI_SUBSCRIBE_PLAYER_STATE            (1u << 4)
+#define CSGO_GSI_SUBSCRIBE_PLAYER_WEAPONS          (1u << 5)
+#define CSGO_GSI_SUBSCRIBE_PROVIDER                (1u << 6)
+#define CSGO_GSI_SUBSCRIBE_ROUND                   (1u << 7)
+#define CSGO_GSI_SUBSCRIBE_ALL_GRENADES            (1u << 8)
+#define CSGO_GSI_SUBSCRIBE_ALL_PLAYERS_ID          (1u << 9)
+#define CSGO_GSI_SUBSCRIBE_ALL_PLAYERS_MATCH_STATS (1u << 10)
+#define CSGO_GSI_SUBSCRIBE_ALL_PLAYERS_POSITION    (1u << 11)
+#define CSGO_GSI_SUBSCRIBE_ALL_PLAYERS_STATE       (1u << 12)
+#define CSGO_GSI_SUBSCRIBE_ALL_PLAYERS_WEAPONS     (1u << 13)
+#define CSGO_GSI_SUBSCRIBE_BOMB                    (1u << 14)
+#define CSGO_GSI_SUBSCRIBE_PHASE_COUNTDOWNS        (1u << 15)
+#define CSGO_GSI_SUBSCRIBE_PLAYER_POSITION         (1u << 16)
+
+/* the subscriptions available in every context */
+#define CSGO_GSI_SUBSCRIBE_UNRESTRICTED 0xffu
+/* the subscriptions only available to spectators */
+#define CSGO_GSI_SUBSCRIBE_SPECTATOR_ONLY 0x1ff00u
+
+typedef struct csgo_gsi_config_builder csgo_gsi_config_builder;
+typedef struct csgo_gsi_server csgo_gsi_server;
+
+/*
+ * the parts of an update most programs need
+ *
+ * strings are the values from the update's JSON, like "CT" or "freezetime", and are null if the
+ * update didn't include them. numbers are 0 if the update didn't include them. everything is
+ * only valid until the callback returns.
+ */
+typedef struct csgo_gsi_update {
+    const char *provider_steam_id;
+    uint64_t provider_timestamp;
+
+    const char *map_name;
+    const char *map_mode;
+    const char *map_phase;
+    uint64_t map_round;
+    uint64_t map_score_ct;
+    uint64_t map_score_t;
+
+    const char *round_phase;
+    const char *round_bomb;
+    const char *round_win_team;
+
+    const char *player_steam_id;
+    const char *player_name;
+    const char *player_activity;
+    const char *player_team;
+    /* the name of the weapon being held, like "weapon_ak47" */
+    const char *player_active_weapon;
+    bool has_player_state;
+    uint64_t player_health;
+    uint64_t player_armor;
+    bool player_helmet;
+    uint64_t player_money;
+    int64_t player_round_kills;
+    bool has_player_match_stats;
+    int64_t player_kills;
+    uint64_t player_assists;
+    uint64_t player_deaths;
+    uint64_t player_mvps;
+    uint64_t player_score;
+} csgo_gsi_update;
+
+/*
+ * callbacks are called on the server's own thread, one at a time. json is the update exactly as
+ * CS:GO sent it, and is nul-terminated.
+ */
+typedef void (*csgo_gsi_json_callback)(const char *json, size_t len, void *user_data);
+typedef void (*csgo_gsi_update_callback)(const csgo_gsi_update *update, void *user_data);
+
+/* the message for the last error on this thread, or null; valid until the next error */
+const char *csgo_gsi_last_error(void);
+
+/* start building a config with the given name */
+csgo_gsi_config_builder *csgo_gsi_config_builder_new(const char *name);
+/* subscribe to every CSGO_GSI_SUBSCRIBE_* bit that's set */
+void csgo_gsi_config_builder_subscribe(csgo_gsi_config_builder *builder, uint32_t subscriptions);
+/* add a key/value pair that has to be sent with every update */
+int csgo_gsi_config_builder_auth(csgo_gsi_config_builder *builder, const char *key, const char *value);
+/* how long CS:GO waits for a response */
+void csgo_gsi_config_builder_timeout(csgo_gsi_config_builder *builder, double seconds);
+/* how long CS:GO collects changes before sending them */
+void csgo_gsi_config_builder_buffer(csgo_gsi_config_builder *builder, double seconds);
+/* how long CS:GO waits between updates */
+void csgo_gsi_config_builder_throttle(csgo_gsi_config_builder *builder, double seconds);
+/* how often CS:GO sends an update even if nothing changed */
+void csgo_gsi_config_builder_heartbeat(csgo_gsi_config_builder *builder, double seconds);
+void csgo_gsi_config_builder_free(csgo_gsi_config_builder *builder);
+
+/*
+ * create a server with the built config, listening on 127.0.0.1 at the given port, or any free
+ * one if it's 0. the builder can be freed afterwards.
+ */
+csgo_gsi_server *csgo_gsi_server_new(const csgo_gsi_config_builder *builder, uint16_t port);
+/* the port the server is listening on, or 0 if the server is null */
+uint16_t csgo_gsi_server_port(const csgo_gsi_server *server);
+/*
+ * install the server's config into the given /path/to/csgo/cfg/ folder, or the autodiscovered
+ * one if it's null. if this isn't called, starting the server installs it automatically.
+ */
+int csgo_gsi_server_install(csgo_gsi_server *server, const char *cfg_folder);
+/* listeners have to be added before the server is started */
+int csgo_gsi_server_add_json_listener(csgo_gsi_server *server, csgo_gsi_json_callback callback, void *user_data);
+int csgo_gsi_server_add_update_listener(csgo_gsi_server *server, csgo_gsi_update_callback callback, void *user_data);
+/* start handling updates on a thread of the server's own; a server can only be started once */
+int csgo_gsi_server_start(csgo_gsi_server *server);
+/*
+ * stop the server, waiting for it to finish handling the updates it's received. fails if it
+ * wasn't running, or if it stopped because of an error.
+ */
+int csgo_gsi_server_stop(csgo_gsi_server *server);
+/* stop the server if it's running, and free it */
+void csgo_gsi_server_free(csgo_gsi_server *server);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CSGO_GSI_H */
@@ -0,0 +1,450 @@
+//! a C API for [csgo-gsi](https://docs.rs/csgo-gsi), for using the server from other languages
+//!
+//! `include/csgo_gsi.h` documents how to use it. every function here is `unsafe`, because it
+//! trusts the pointers it's given.
+
+// the functions are only meant to be called from C, and the header is where their safety rules are
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+use csgo_gsi::{Error, GSIConfigBuilder, GSIServer, ShutdownHandle, Subscription, Update};
+use csgo_gsi::update::player::WeaponState;
+
+/// the order of the `CSGO_GSI_SUBSCRIBE_*` bits
+const SUBSCRIPTIONS: [Subscription; 17] = [
+    Subscription::MapRoundWins,
+    Subscription::Map,
+    Subscription::PlayerID,
+    Subscription::PlayerMatchStats,
+    Subscription::PlayerState,
+    Subscription::PlayerWeapons,
+    Subscription::Provider,
+    Subscription::Round,
+    Subscription::AllGrenades,
+    Subscription::AllPlayersID,
+    Subscription::AllPlayersMatchStats,
+    Subscription::AllPlayersPosition,
+    Subscription::AllPlayersState,
+    Subscription::AllPlayersWeapons,
+    Subscription::Bomb,
+    Subscription::PhaseCountdowns,
+    Subscription::PlayerPosition,
+];
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error<E: ToString>(error: E) {
+    let message = error.to_string().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// a string from C, or `None` (and the last error set) if it's null or not UTF-8
+unsafe fn string<'a>(string: *const c_char, what: &str) -> Option<&'a str> {
+    if string.is_null() {
+        set_last_error(format!("{} was null", what));
+        return None;
+    }
+    let string = CStr::from_ptr(string).to_str();
+    if string.is_err() {
+        set_last_error(format!("{} wasn't UTF-8", what));
+    }
+    string.ok()
+}
+
+/// what a pointer from C points to, or `None` (and the last error set) if it's null
+unsafe fn non_null<'a, T>(pointer: *const T, what: &str) -> Option<&'a T> {
+    let value = pointer.as_ref();
+    if value.is_none() {
+        set_last_error(format!("{} was null", what));
+    }
+    value
+}
+
+/// like `non_null`, but mutable
+unsafe fn non_null_mut<'a, T>(pointer: *mut T, what: &str) -> Option<&'a mut T> {
+    let value = pointer.as_mut();
+    if value.is_none() {
+        set_last_error(format!("{} was null", what));
+    }
+    value
+}
+
+/// the name an enum has in update JSON, like `"CT"` or `"freezetime"`
+fn serde_name<T: Serialize>(value: &T) -> Option<String> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// a server being moved onto its own thread by `csgo_gsi_server_start`
+struct StartingServer(Box<GSIServer>, Runtime);
+
+// `GSIServer` isn't `Send` because its listeners don't have to be. the only listeners this crate
+// ever adds are the closures in `csgo_gsi_server_add_*_listener`, which hold nothing but a C
+// function pointer and its `user_data`, and the header says callbacks are called on the server's
+// own thread, so it's up to C whether that's safe. nothing else, like a script host, can be added
+// to a server made here.
+unsafe impl Send for StartingServer {}
+
+/// `csgo_gsi_config_builder`
+pub struct ConfigBuilder(GSIConfigBuilder);
+
+/// `csgo_gsi_server`
+pub struct Server {
+    state: ServerState,
+    port: u16,
+}
+
+enum ServerState {
+    Ready(Box<GSIServer>, Runtime),
+    Running(ShutdownHandle, JoinHandle<Result<(), Error>>),
+    Stopped,
+}
+
+/// `csgo_gsi_update`, which is only valid during the callback it's passed to
+#[repr(C)]
+pub struct FlatUpdate {
+    provider_steam_id: *const c_char,
+    provider_timestamp: u64,
+    map_name: *const c_char,
+    map_mode: *const c_char,
+    map_phase: *const c_char,
+    map_round: u64,
+    map_score_ct: u64,
+    map_score_t: u64,
+    round_phase: *const c_char,
+    round_bomb: *const c_char,
+    round_win_team: *const c_char,
+    player_steam_id: *const c_char,
+    player_name: *const c_char,
+    player_activity: *const c_char,
+    player_team: *const c_char,
+    player_active_weapon: *const c_char,
+    has_player_state: bool,
+    player_health: u64,
+    player_armor: u64,
+    player_helmet: bool,
+    player_money: u64,
+    player_round_kills: i64,
+    has_player_match_stats: bool,
+    player_kills: i64,
+    player_assists: u64,
+    player_deaths: u64,
+    player_mvps: u64,
+    player_score: u64,
+}
+
+/// flatten an update, and pass it to the given function while its strings are still alive
+fn with_flat_update<F: FnOnce(&FlatUpdate)>(update: &Update, f: F) {
+    let mut strings = vec![];
+    let mut string = |value: Option<String>| match value.and_then(|value| CString::new(value).ok()) {
+        Some(value) => {
+            // the string's bytes don't move when `strings` grows
+            let pointer = value.as_ptr();
+            strings.push(value);
+            pointer
+        }
+        None => ptr::null(),
+    };
+    let provider = update.provider.as_ref();
+    let map = update.map.as_ref();
+    let round = update.round.as_ref();
+    let player = update.player.as_ref();
+    let state = player.and_then(|player| player.state.as_ref());
+    let match_stats = player.and_then(|player| player.match_stats.as_ref());
+    let active_weapon = player.and_then(|player| player.weapons.values()
+        .find(|weapon| matches!(weapon.state, WeaponState::Active | WeaponState::Reloading)));
+    let flat = FlatUpdate {
+        provider_steam_id: string(provider.map(|provider| provider.steam_id.clone())),
+        provider_timestamp: provider.map_or(0, |provider| provider.timestamp),
+        map_name: string(map.map(|map| map.name.clone())),
+        map_mode: string(map.and_then(|map| serde_name(&map.mode))),
+        map_phase: string(map.and_then(|map| serde_name(&map.phase))),
+        map_round: map.map_or(0, |map| map.round),
+        map_score_ct: map.map_or(0, |map| map.team_ct.score),
+        map_score_t: map.map_or(0, |map| map.team_t.score),
+        round_phase: string(round.and_then(|round| serde_name(&round.phase))),
+        round_bomb: string(round.and_then(|round| round.bomb.as_ref()).and_then(serde_name)),
+        round_win_team: string(round.and_then(|round| round.win_team.as_ref()).and_then(serde_name)),
+        player_steam_id: string(player.map(|player| player.steam_id.clone())),
+        player_name: string(player.map(|player| player.name.clone())),
+        player_activity: string(player.and_then(|player| serde_name(&player.activity))),
+        player_team: string(player.and_then(|player| player.team.as_ref()).and_then(serde_name)),
+        player_active_weapon: string(active_weapon.map(|weapon| weapon.name.clone())),
+        has_player_state: state.is_some(),
+        player_health: state.map_or(0, |state| state.health),
+        player_armor: state.map_or(0, |state| state.armor),
+        player_helmet: state.is_some_and(|state| state.helmet),
+        player_money: state.map_or(0, |state| state.money),
+        player_round_kills: state.map_or(0, |state| state.round_kills),
+        has_player_match_stats: match_stats.is_some(),
+        player_kills: match_stats.map_or(0, |stats| stats.kills),
+        player_assists: match_stats.map_or(0, |stats| stats.assists),
+        player_deaths: match_stats.map_or(0, |stats| stats.deaths),
+        player_mvps: match_stats.map_or(0, |stats| stats.mvps),
+        player_score: match_stats.map_or(0, |stats| stats.score),
+    };
+    f(&flat);
+}
+
+/// the message for the last error on this thread, or null
+#[no_mangle]
+pub extern "C" fn csgo_gsi_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// start building a config with the given name
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_config_builder_new(name: *const c_char) -> *mut ConfigBuilder {
+    match string(name, "name") {
+        Some(name) => Box::into_raw(Box::new(ConfigBuilder(GSIConfigBuilder::new(name)))),
+        None => ptr::null_mut(),
+    }
+}
+
+/// subscribe to every `CSGO_GSI_SUBSCRIBE_*` bit that's set
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_config_builder_subscribe(builder: *mut ConfigBuilder, subscriptions: u32) {
+    let builder = match non_null_mut(builder, "builder") {
+        Some(builder) => &mut builder.0,
+        None => return,
+    };
+    for (bit, subscription) in SUBSCRIPTIONS.iter().enumerate() {
+        if subscriptions & (1 << bit) != 0 {
+            builder.subscribe(*subscription);
+        }
+    }
+}
+
+/// add a key/value pair that has to be sent with every update
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_config_builder_auth(builder: *mut ConfigBuilder, key: *const c_char, value: *const c_char) -> c_int {
+    let builder = match non_null_mut(builder, "builder") {
+        Some(builder) => builder,
+        None => return -1,
+    };
+    match (string(key, "key"), string(value, "value")) {
+        (Some(key), Some(value)) => {
+            builder.0.auth(key, value);
+            0
+        }
+        _ => -1,
+    }
+}
+
+macro_rules! duration_setter {
+    ($name:ident, $method:ident, $doc:literal) => {
+        #[doc = $doc]
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(builder: *mut ConfigBuilder, seconds: f64) {
+            if let Some(builder) = non_null_mut(builder, "builder") {
+                builder.0.$method(Duration::from_secs_f64(seconds.max(0.0)));
+            }
+        }
+    };
+}
+
+duration_setter!(csgo_gsi_config_builder_timeout, timeout, "set how long CS:GO waits for a response");
+duration_setter!(csgo_gsi_config_builder_buffer, buffer, "set how long CS:GO collects changes before sending them");
+duration_setter!(csgo_gsi_config_builder_throttle, throttle, "set how long CS:GO waits between updates");
+duration_setter!(csgo_gsi_config_builder_heartbeat, heartbeat, "set how often CS:GO sends an update even if nothing changed");
+
+/// free a config builder
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_config_builder_free(builder: *mut ConfigBuilder) {
+    if !builder.is_null() {
+        drop(Box::from_raw(builder));
+    }
+}
+
+/// create a server with the built config, listening on the given port (or any free one, if 0)
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_new(builder: *const ConfigBuilder, port: u16) -> *mut Server {
+    let builder = match non_null(builder, "builder") {
+        Some(builder) => builder,
+        None => return ptr::null_mut(),
+    };
+    let config = match builder.0.try_build() {
+        Ok(config) => config,
+        Err(error) => {
+            set_last_error(error);
+            return ptr::null_mut();
+        }
+    };
+    let mut runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            set_last_error(Error::ServerError { description: "failed to start runtime", cause: Some(Box::new(error)) });
+            return ptr::null_mut();
+        }
+    };
+    let mut server = Box::new(GSIServer::new(config, port));
+    match runtime.block_on(server.bind()) {
+        Ok(addr) => Box::into_raw(Box::new(Server { state: ServerState::Ready(server, runtime), port: addr.port() })),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// the port the server is listening on, or 0 if the server is null
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_port(server: *const Server) -> u16 {
+    non_null(server, "server").map_or(0, |server| server.port)
+}
+
+/// the server, if it hasn't been started yet
+unsafe fn ready<'a>(server: *mut Server) -> Option<&'a mut GSIServer> {
+    match &mut non_null_mut(server, "server")?.state {
+        ServerState::Ready(server, _) => Some(server),
+        _ => {
+            set_last_error("server has already been started");
+            None
+        }
+    }
+}
+
+/// install the server's config into the given `/path/to/csgo/cfg/` folder, or the autodiscovered one if null
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_install(server: *mut Server, cfg_folder: *const c_char) -> c_int {
+    let server = match ready(server) {
+        Some(server) => server,
+        None => return -1,
+    };
+    let installed = match cfg_folder.is_null() {
+        true => server.install(),
+        false => match string(cfg_folder, "cfg folder") {
+            Some(cfg_folder) => server.install_into(cfg_folder),
+            None => return -1,
+        },
+    };
+    match installed {
+        Ok(()) => 0,
+        Err(error) => {
+            set_last_error(error);
+            -1
+        }
+    }
+}
+
+/// `csgo_gsi_json_callback`
+pub type JsonCallback = Option<unsafe extern "C" fn(json: *const c_char, len: usize, user_data: *mut c_void)>;
+
+/// `csgo_gsi_update_callback`
+pub type UpdateCallback = Option<unsafe extern "C" fn(update: *const FlatUpdate, user_data: *mut c_void)>;
+
+/// call the given function with the JSON of every update, exactly as CS:GO sent it
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_add_json_listener(server: *mut Server, callback: JsonCallback, user_data: *mut c_void) -> c_int {
+    let (server, callback) = match (ready(server), callback) {
+        (Some(server), Some(callback)) => (server, callback),
+        (_, None) => {
+            set_last_error("callback was null");
+            return -1;
+        }
+        _ => return -1,
+    };
+    server.add_capture_listener(move |captured| match CString::new(captured.raw.as_str()) {
+        Ok(json) => callback(json.as_ptr(), captured.raw.len(), user_data),
+        Err(error) => log::warn!("couldn't pass an update to C: {}", error),
+    });
+    0
+}
+
+/// call the given function with a flattened version of every update
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_add_update_listener(server: *mut Server, callback: UpdateCallback, user_data: *mut c_void) -> c_int {
+    let (server, callback) = match (ready(server), callback) {
+        (Some(server), Some(callback)) => (server, callback),
+        (_, None) => {
+            set_last_error("callback was null");
+            return -1;
+        }
+        _ => return -1,
+    };
+    server.add_listener(move |update| with_flat_update(update, |flat| callback(flat, user_data)));
+    0
+}
+
+/// start the server on a thread of its own
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_start(server: *mut Server) -> c_int {
+    let server = match non_null_mut(server, "server") {
+        Some(server) => server,
+        None => return -1,
+    };
+    match std::mem::replace(&mut server.state, ServerState::Stopped) {
+        ServerState::Ready(gsi_server, runtime) => {
+            let shutdown = gsi_server.shutdown_handle();
+            let starting = StartingServer(gsi_server, runtime);
+            let thread = thread::spawn(move || {
+                let StartingServer(gsi_server, mut runtime) = starting;
+                runtime.block_on(gsi_server.run())
+            });
+            server.state = ServerState::Running(shutdown, thread);
+            0
+        }
+        state => {
+            server.state = state;
+            set_last_error("server has already been started");
+            -1
+        }
+    }
+}
+
+/// stop the server, waiting for it to finish handling the updates it's received
+///
+/// fails if it wasn't running, or if it stopped because of an error.
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_stop(server: *mut Server) -> c_int {
+    let server = match non_null_mut(server, "server") {
+        Some(server) => server,
+        None => return -1,
+    };
+    match std::mem::replace(&mut server.state, ServerState::Stopped) {
+        ServerState::Running(shutdown, thread) => {
+            shutdown.shutdown();
+            match thread.join() {
+                Ok(Ok(())) => 0,
+                Ok(Err(error)) => {
+                    set_last_error(error);
+                    -1
+                }
+                Err(_) => {
+                    set_last_error("server thread panicked");
+                    -1
+                }
+            }
+        }
+        state => {
+            server.state = state;
+            set_last_error("server isn't running");
+            -1
+        }
+    }
+}
+
+/// stop the server if it's running, and free it
+#[no_mangle]
+pub unsafe extern "C" fn csgo_gsi_server_free(server: *mut Server) {
+    if server.is_null() {
+        return;
+    }
+    if let ServerState::Running(..) = (*server).state {
+        csgo_gsi_server_stop(server);
+    }
+    drop(Box::from_raw(server));
+}
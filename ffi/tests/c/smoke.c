@@ -0,0 +1,137 @@
+/*
+ * runs a server through the C API and sends it one update
+ *
+ *     smoke /path/to/update.json /path/to/cfg/folder
+ */
+
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "csgo_gsi.h"
+
+#define CHECK(condition) do { \
+    if (!(condition)) { \
+        const char *error = csgo_gsi_last_error(); \
+        fprintf(stderr, "%s:%d: check failed: %s (last error: %s)\n", __FILE__, __LINE__, #condition, error ? error : "none"); \
+        exit(1); \
+    } \
+} while (0)
+
+struct received {
+    size_t json_updates;
+    size_t json_len;
+    size_t updates;
+    char map_name[64];
+    char player_team[8];
+    uint64_t map_round;
+    uint64_t player_money;
+    int64_t player_kills;
+};
+
+static void on_json(const char *json, size_t len, void *user_data) {
+    struct received *received = user_data;
+    received->json_updates++;
+    received->json_len = len;
+    CHECK(strlen(json) == len);
+}
+
+static void on_update(const csgo_gsi_update *update, void *user_data) {
+    struct received *received = user_data;
+    received->updates++;
+    CHECK(update->map_name && update->player_team && update->has_player_state && update->has_player_match_stats);
+    snprintf(received->map_name, sizeof received->map_name, "%s", update->map_name);
+    snprintf(received->player_team, sizeof received->player_team, "%s", update->player_team);
+    received->map_round = update->map_round;
+    received->player_money = update->player_money;
+    received->player_kills = update->player_kills;
+}
+
+static char *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    CHECK(file);
+    fseek(file, 0, SEEK_END);
+    *len = (size_t) ftell(file);
+    fseek(file, 0, SEEK_SET);
+    char *contents = malloc(*len + 1);
+    CHECK(contents && fread(contents, 1, *len, file) == *len);
+    contents[*len] = '\0';
+    fclose(file);
+    return contents;
+}
+
+/* POST the body to the server, returning the HTTP status */
+static int post(uint16_t port, const char *body, size_t len) {
+    int sock = socket(AF_INET, SOCK_STREAM, 0);
+    CHECK(sock >= 0);
+    struct sockaddr_in addr = { .sin_family = AF_INET, .sin_port = htons(port) };
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    CHECK(connect(sock, (struct sockaddr *) &addr, sizeof addr) == 0);
+    char head[256];
+    int head_len = snprintf(head, sizeof head,
+        "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\nContent-Length: %zu\r\nConnection: close\r\n\r\n", len);
+    CHECK(write(sock, head, (size_t) head_len) == head_len);
+    CHECK(write(sock, body, len) == (ssize_t) len);
+    char response[64] = {0};
+    CHECK(read(sock, response, sizeof response - 1) > 12);
+    close(sock);
+    return atoi(response + 9);
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 3);
+    size_t len;
+    char *update = read_file(argv[1], &len);
+
+    CHECK(csgo_gsi_config_builder_new(NULL) == NULL);
+    CHECK(csgo_gsi_last_error() != NULL);
+    CHECK(csgo_gsi_config_builder_auth(NULL, "token", "hunter2") == -1);
+    CHECK(strcmp(csgo_gsi_last_error(), "builder was null") == 0);
+    csgo_gsi_config_builder_subscribe(NULL, CSGO_GSI_SUBSCRIBE_UNRESTRICTED);
+    csgo_gsi_config_builder_heartbeat(NULL, 30.0);
+    CHECK(csgo_gsi_server_new(NULL, 0) == NULL);
+    CHECK(csgo_gsi_server_port(NULL) == 0);
+    CHECK(csgo_gsi_server_install(NULL, NULL) == -1);
+    CHECK(strcmp(csgo_gsi_last_error(), "server was null") == 0);
+    CHECK(csgo_gsi_server_add_json_listener(NULL, on_json, NULL) == -1);
+    CHECK(csgo_gsi_server_add_update_listener(NULL, on_update, NULL) == -1);
+    CHECK(csgo_gsi_server_start(NULL) == -1);
+    CHECK(csgo_gsi_server_stop(NULL) == -1);
+
+    csgo_gsi_config_builder *builder = csgo_gsi_config_builder_new("c smoke");
+    CHECK(builder);
+    csgo_gsi_config_builder_subscribe(builder, CSGO_GSI_SUBSCRIBE_UNRESTRICTED);
+    CHECK(csgo_gsi_config_builder_auth(builder, "token", "hunter2") == 0);
+    csgo_gsi_config_builder_heartbeat(builder, 30.0);
+    csgo_gsi_server *server = csgo_gsi_server_new(builder, 0);
+    csgo_gsi_config_builder_free(builder);
+    CHECK(server);
+    uint16_t port = csgo_gsi_server_port(server);
+    CHECK(port != 0);
+
+    CHECK(csgo_gsi_server_install(server, argv[2]) == 0);
+    struct received received = {0};
+    CHECK(csgo_gsi_server_add_json_listener(server, on_json, &received) == 0);
+    CHECK(csgo_gsi_server_add_update_listener(server, on_update, &received) == 0);
+    CHECK(csgo_gsi_server_stop(server) == -1);
+    CHECK(csgo_gsi_server_start(server) == 0);
+    CHECK(csgo_gsi_server_add_json_listener(server, on_json, &received) == -1);
+
+    CHECK(post(port, update, len) == 200);
+    CHECK(csgo_gsi_server_stop(server) == 0);
+    CHECK(csgo_gsi_server_start(server) == -1);
+    csgo_gsi_server_free(server);
+
+    CHECK(received.json_updates == 1 && received.json_len == len);
+    CHECK(received.updates == 1);
+    CHECK(strcmp(received.map_name, "de_dust2") == 0);
+    CHECK(strcmp(received.player_team, "CT") == 0);
+    CHECK(received.map_round == 3 && received.player_money == 3150 && received.player_kills == 3);
+    free(update);
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,42 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// compile `tests/c/smoke.c` against the shared library and run it
+#[test]
+#[cfg(target_os = "linux")]
+fn test_c_program() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // the shared library is built alongside this test's own executable
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    assert!(deps.join("libcsgo_gsi_ffi.so").exists(), "shared library wasn't built in {}", deps.display());
+    let folder = tempfile::tempdir().unwrap();
+    let program = folder.path().join("smoke");
+
+    let compiled = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg("-std=c99")
+        .arg("-D_POSIX_C_SOURCE=200809L")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I").arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/smoke.c"))
+        .arg("-o").arg(&program)
+        .arg("-L").arg(&deps)
+        .arg("-lcsgo_gsi_ffi")
+        .status()
+        .expect("couldn't run the C compiler");
+    assert!(compiled.success());
+
+    let cfg_folder = folder.path().join("cfg");
+    std::fs::create_dir(&cfg_folder).unwrap();
+    // cargo's own library path can have a copy built with different features
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &deps)
+        .arg(manifest_dir.join("../tests/fixtures/update.json"))
+        .arg(&cfg_folder)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+    assert!(cfg_folder.join("gamestate_integration_c smoke.cfg").exists());
+}
@@ -0,0 +1,336 @@
+//! command-line interface for installing, listening to, recording and replaying CS:GO GSI updates
+
+use std::fs;
+use std::net::{IpAddr, TcpListener};
+use std::path::PathBuf;
+use std::process;
+
+use csgo_gsi::{GSIConfig, GSIConfigBuilder, GSIServer, Error, Subscription};
+use csgo_gsi::capture::{Recorder, Rotation};
+use csgo_gsi::replay::{Replayer, Speed};
+use structopt::StructOpt;
+
+const MANAGED_MARKER: &str = "Managed by the csgo-gsi Rust library";
+
+#[derive(StructOpt)]
+struct ServiceOpts {
+    /// service name, used in the installed config file's name
+    #[structopt(long, default_value = "csgo-gsi")]
+    name: String,
+    /// port to listen on
+    #[structopt(long, default_value = "31337")]
+    port: u16,
+    /// address to listen on (anything but loopback requires --auth-token)
+    #[structopt(long, default_value = "127.0.0.1")]
+    bind: IpAddr,
+    /// URI to tell CS:GO to send updates to, if the game is on another machine
+    #[structopt(long)]
+    uri: Option<String>,
+    /// token CS:GO must send with every update
+    #[structopt(long)]
+    auth_token: Option<String>,
+    /// CS:GO cfg folder (autodiscovered if not given)
+    #[structopt(long, parse(from_os_str))]
+    cfg_folder: Option<PathBuf>,
+}
+
+impl ServiceOpts {
+    fn config(&self) -> Result<GSIConfig, Error> {
+        let mut builder = GSIConfigBuilder::new(self.name.clone());
+        builder.subscribe_multiple(Subscription::UNRESTRICTED);
+        if let Some(token) = &self.auth_token {
+            builder.auth("token", token.clone());
+        }
+        builder.try_build()
+    }
+
+    async fn server(&self) -> Result<GSIServer, Error> {
+        let mut server = GSIServer::new(self.config()?, self.port);
+        server.bind_to(self.bind);
+        // installing needs to know the port, which isn't picked until binding if it's 0
+        server.bind().await?;
+        server.on_error(|err| eprintln!("error: {}", err));
+        if let Some(uri) = &self.uri {
+            server.advertise_uri(uri.clone());
+        }
+        match &self.cfg_folder {
+            Some(cfg_folder) => server.install_into(cfg_folder)?,
+            None => server.install()?,
+        }
+        Ok(server)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Pretty,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "pretty" => Ok(Format::Pretty),
+            _ => Err(format!("unknown format {:?}, expected json or pretty", s)),
+        }
+    }
+}
+
+fn parse_speed(s: &str) -> Result<Speed, String> {
+    match s {
+        "realtime" => Ok(Speed::RealTime),
+        "max" => Ok(Speed::AsFastAsPossible),
+        _ => s.parse::<f64>()
+            .map(Speed::Accelerated)
+            .map_err(|_| format!("unknown speed {:?}, expected realtime, max, or a multiplier", s)),
+    }
+}
+
+/// install, listen to, record and replay CS:GO Game State Integration updates
+#[derive(StructOpt)]
+#[structopt(name = "csgo-gsi")]
+enum Command {
+    /// install a config file that subscribes to everything
+    Install {
+        #[structopt(flatten)]
+        service: ServiceOpts,
+    },
+    /// remove a previously installed config file
+    Uninstall {
+        #[structopt(flatten)]
+        service: ServiceOpts,
+    },
+    /// list the GSI config files in the CS:GO cfg folder
+    ListConfigs {
+        /// CS:GO cfg folder (autodiscovered if not given)
+        #[structopt(long, parse(from_os_str))]
+        cfg_folder: Option<PathBuf>,
+    },
+    /// print every update as it arrives
+    Listen {
+        #[structopt(flatten)]
+        service: ServiceOpts,
+        /// output format: json or pretty
+        #[structopt(long, default_value = "pretty")]
+        format: Format,
+    },
+    /// record every update to a newline-delimited JSON capture file, until Ctrl-C is pressed
+    Record {
+        /// capture file to write
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(flatten)]
+        service: ServiceOpts,
+        /// gzip-compress the capture file
+        #[structopt(long)]
+        gzip: bool,
+        /// start a new file after this many bytes
+        #[structopt(long, conflicts_with = "rotate-per-match")]
+        rotate_size: Option<u64>,
+        /// start a new file for every match
+        #[structopt(long)]
+        rotate_per_match: bool,
+    },
+    /// replay a capture file against a running server
+    Replay {
+        /// capture file to read
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// URI of the server to replay to
+        #[structopt(long, default_value = "http://127.0.0.1:31337/")]
+        uri: String,
+        /// realtime, max, or a multiplier like 4
+        #[structopt(long, default_value = "realtime", parse(try_from_str = parse_speed))]
+        speed: Speed,
+        /// skip ahead to this round
+        #[structopt(long)]
+        round: Option<u64>,
+    },
+    /// check that every update in a capture file still parses, and report exactly where any don't,
+    /// with the value found there and what was expected instead
+    Audit {
+        /// capture file to read
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// check that everything needed to receive updates is in place
+    Doctor {
+        /// port to check
+        #[structopt(long, default_value = "31337")]
+        port: u16,
+    },
+}
+
+/// run the server until it fails or Ctrl-C is pressed, letting it finish up either way
+async fn run_until_ctrl_c(server: GSIServer) -> Result<(), Error> {
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            shutdown.shutdown();
+        }
+    });
+    server.run().await
+}
+
+fn cfg_folder_or_discover(cfg_folder: Option<PathBuf>) -> Result<PathBuf, Error> {
+    match cfg_folder {
+        Some(cfg_folder) => Ok(cfg_folder),
+        None => csgo_gsi::discover_cfg_folder(),
+    }
+}
+
+fn list_configs(cfg_folder: Option<PathBuf>) -> Result<(), Error> {
+    let cfg_folder = cfg_folder_or_discover(cfg_folder)?;
+    let entries = fs::read_dir(&cfg_folder)
+        .map_err(|err| Error::ConfigInstallError { description: "could not read cfg folder", cause: Some(Box::new(err)) })?;
+    let mut names = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("gamestate_integration_") && name.ends_with(".cfg")))
+        .collect::<Vec<_>>();
+    names.sort();
+    for path in names {
+        let managed = fs::read_to_string(&path)
+            .map(|contents| contents.contains(MANAGED_MARKER))
+            .unwrap_or(false);
+        let name = path.file_name().expect("path came from read_dir").to_string_lossy();
+        if managed {
+            println!("{} (managed by csgo-gsi)", name);
+        } else {
+            println!("{}", name);
+        }
+    }
+    Ok(())
+}
+
+fn audit(file: PathBuf) -> Result<bool, Error> {
+    let failures = csgo_gsi::replay::audit(file)?;
+    for (line, err) in &failures {
+        match err {
+            Error::UpdateSchema { path, value, cause, .. } => {
+                let value = value.as_ref().map_or_else(|| "nothing".to_string(), |value| value.to_string());
+                println!("line {}: {}: got {} ({})", line, path, value, cause);
+            }
+            err => println!("line {}: {}", line, err),
+        }
+    }
+    Ok(failures.is_empty())
+}
+
+fn doctor(port: u16) -> bool {
+    let mut healthy = true;
+    match csgo_gsi::get_library_folders() {
+        Ok(path) if path.exists() => println!("ok: found Steam library list at {}", path.display()),
+        Ok(path) => {
+            println!("FAIL: Steam library list not found at {}", path.display());
+            healthy = false;
+        }
+        Err(err) => {
+            println!("FAIL: {}", err);
+            healthy = false;
+        }
+    }
+    match csgo_gsi::discover_cfg_folder() {
+        Ok(cfg_folder) => {
+            println!("ok: found CS:GO cfg folder at {}", cfg_folder.display());
+            let probe = cfg_folder.join("gamestate_integration_csgo-gsi-doctor.cfg");
+            match fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe)) {
+                Ok(()) => println!("ok: cfg folder is writable"),
+                Err(err) => {
+                    println!("FAIL: cfg folder is not writable: {}", err);
+                    healthy = false;
+                }
+            }
+        }
+        Err(err) => {
+            println!("FAIL: {}", err);
+            healthy = false;
+        }
+    }
+    match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => println!("ok: port {} is available", port),
+        Err(err) => {
+            println!("FAIL: port {} is not available: {}", port, err);
+            healthy = false;
+        }
+    }
+    healthy
+}
+
+#[tokio::main]
+async fn main() {
+    let result = match Command::from_args() {
+        Command::Install { service } => service.server().await.map(|_| ()),
+        Command::Uninstall { service } => match service.config() {
+            Ok(config) => {
+                let mut server = GSIServer::new(config, service.port);
+                match service.cfg_folder {
+                    Some(cfg_folder) => server.uninstall_from(cfg_folder),
+                    None => server.uninstall(),
+                }
+            }
+            Err(err) => Err(err),
+        },
+        Command::ListConfigs { cfg_folder } => list_configs(cfg_folder),
+        Command::Listen { service, format } => {
+            match service.server().await {
+                Ok(mut server) => {
+                    server.add_listener(move |update| match format {
+                        Format::Json => match serde_json::to_string(update) {
+                            Ok(json) => println!("{}", json),
+                            Err(err) => eprintln!("{}", err),
+                        },
+                        Format::Pretty => println!("{:#?}", update),
+                    });
+                    server.run().await
+                }
+                Err(err) => Err(err),
+            }
+        }
+        Command::Record { file, service, gzip, rotate_size, rotate_per_match } => {
+            let rotation = match (rotate_size, rotate_per_match) {
+                (Some(size), _) => Rotation::Size(size),
+                (None, true) => Rotation::Match,
+                (None, false) => Rotation::Never,
+            };
+            match service.server().await {
+                Ok(mut server) => {
+                    server.add_recorder(Recorder::new(file).gzip(gzip).rotation(rotation));
+                    run_until_ctrl_c(server).await
+                }
+                Err(err) => Err(err),
+            }
+        }
+        Command::Replay { file, uri, speed, round } => {
+            let replayer = Replayer::open(file).map(|replayer| replayer.speed(speed));
+            let replayer = match (replayer, round) {
+                (Ok(replayer), Some(round)) => replayer.seek_to_round(round),
+                (replayer, _) => replayer,
+            };
+            match replayer {
+                Ok(replayer) => replayer.replay_to(&uri).await,
+                Err(err) => Err(err),
+            }
+        }
+        Command::Audit { file } => match audit(file) {
+            Ok(true) => Ok(()),
+            Ok(false) => process::exit(1),
+            Err(err) => Err(err),
+        },
+        Command::Doctor { port } => {
+            if !doctor(port) {
+                process::exit(1);
+            }
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
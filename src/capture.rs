@@ -0,0 +1,249 @@
+//! recording updates to newline-delimited JSON capture files
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fehler::throws;
+use flate2::write::GzEncoder;
+use serde::{Serialize, Deserialize};
+
+use crate::Error;
+use crate::update::{Update, map};
+
+/// a single update as it was received over the wire
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapturedUpdate {
+    /// when the update was received, in milliseconds since the Unix epoch
+    pub received_at: u64,
+    /// the raw request body
+    pub raw: String,
+    /// the parsed update
+    pub update: Update,
+}
+
+impl CapturedUpdate {
+    /// capture an update that was received just now
+    pub fn now<S: Into<String>>(raw: S, update: Update) -> Self {
+        let received_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            received_at,
+            raw: raw.into(),
+            update,
+        }
+    }
+}
+
+/// when to start writing to a new capture file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// keep writing to the same file forever
+    Never,
+    /// start a new file once this many (uncompressed) bytes have been written to the current one
+    Size(u64),
+    /// start a new file whenever a new match starts
+    Match,
+}
+
+/// the bits of map info that identify a match
+#[derive(Clone, Debug, PartialEq)]
+struct MatchKey {
+    name: String,
+    phase: map::Phase,
+    round: u64,
+}
+
+impl MatchKey {
+    fn of(update: &Update) -> Option<Self> {
+        update.map.as_ref().map(|map| MatchKey {
+            name: map.name.clone(),
+            phase: map.phase,
+            round: map.round,
+        })
+    }
+
+    fn starts_new_match_after(&self, previous: &MatchKey) -> bool {
+        self.name != previous.name
+            || self.round < previous.round
+            || (previous.phase == map::Phase::GameOver && self.phase != map::Phase::GameOver)
+    }
+}
+
+/// an open capture file
+enum Writer {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Writer {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Writer::Plain(file) => file.write_all(buf),
+            Writer::Gzip(file) => file.write_all(buf),
+        }
+    }
+
+    /// write out everything buffered so far, so the file can be read up to here even if it's
+    /// never finished
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Writer::Plain(file) => file.flush(),
+            // a sync flush, which ends the compressed data so far on a byte boundary
+            Writer::Gzip(file) => file.flush(),
+        }
+    }
+
+    /// write out everything that's buffered, and the gzip trailer if there is one
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Writer::Plain(mut file) => file.flush(),
+            Writer::Gzip(file) => file.finish()?.flush(),
+        }
+    }
+}
+
+/// writes each update it's given to a newline-delimited JSON capture file
+///
+/// every line of the file is a [`CapturedUpdate`](struct.CapturedUpdate.html). each update is
+/// flushed as it's recorded, so if the process is killed, the file can still be read up to the
+/// last one, but a gzip file is only finished properly when the recorder rotates to a new file or
+/// is [closed](#method.close).
+pub struct Recorder {
+    path: PathBuf,
+    gzip: bool,
+    rotation: Rotation,
+    writer: Option<Writer>,
+    file_index: usize,
+    bytes_written: u64,
+    last_match: Option<MatchKey>,
+}
+
+impl Recorder {
+    /// create a recorder that writes to the given file (which will be overwritten)
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            gzip: false,
+            rotation: Rotation::Never,
+            writer: None,
+            file_index: 0,
+            bytes_written: 0,
+            last_match: None,
+        }
+    }
+
+    /// gzip-compress capture files (default is false)
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// when to start a new capture file (default is never)
+    ///
+    /// rotated files have their index inserted before the extension, so `capture.ndjson` is followed
+    /// by `capture.1.ndjson`, `capture.2.ndjson`, etc.
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// the file currently being written to
+    pub fn current_path(&self) -> PathBuf {
+        if self.file_index == 0 {
+            return self.path.clone();
+        }
+        let file_name = self.path.file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_name = match file_name.find('.') {
+            Some(dot) => format!("{}.{}{}", &file_name[..dot], self.file_index, &file_name[dot..]),
+            None => format!("{}.{}", file_name, self.file_index),
+        };
+        self.path.with_file_name(file_name)
+    }
+
+    #[throws]
+    fn open(&mut self) -> &mut Writer {
+        if self.writer.is_none() {
+            let file = File::create(self.current_path())
+                .map_err(|err| Error::CaptureError { description: "failed to create capture file", cause: Some(Box::new(err)) })?;
+            let file = BufWriter::new(file);
+            let writer = if self.gzip {
+                Writer::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            } else {
+                Writer::Plain(file)
+            };
+            self.writer = Some(writer);
+            self.bytes_written = 0;
+        }
+        self.writer.as_mut().expect("writer was just opened")
+    }
+
+    #[throws]
+    fn finish(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()
+                .map_err(|err| Error::CaptureError { description: "failed to finish capture file", cause: Some(Box::new(err)) })?;
+        }
+    }
+
+    #[throws]
+    fn rotate(&mut self) {
+        if self.writer.is_some() {
+            self.finish()?;
+            self.file_index += 1;
+        }
+    }
+
+    /// write a single captured update
+    #[throws]
+    pub fn record(&mut self, captured: &CapturedUpdate) {
+        let match_key = MatchKey::of(&captured.update);
+        if self.rotation == Rotation::Match {
+            if let (Some(current), Some(previous)) = (&match_key, &self.last_match) {
+                if current.starts_new_match_after(previous) {
+                    self.rotate()?;
+                }
+            }
+        }
+        if match_key.is_some() {
+            self.last_match = match_key;
+        }
+
+        let mut line = serde_json::to_vec(captured)
+            .map_err(|err| Error::CaptureError { description: "failed to serialize update", cause: Some(Box::new(err)) })?;
+        line.push(b'\n');
+        let writer = self.open()?;
+        writer.write_all(&line)
+            .and_then(|_| writer.flush())
+            .map_err(|err| Error::CaptureError { description: "failed to write to capture file", cause: Some(Box::new(err)) })?;
+        self.bytes_written += line.len() as u64;
+
+        if let Rotation::Size(max_size) = self.rotation {
+            if self.bytes_written >= max_size {
+                self.rotate()?;
+            }
+        }
+    }
+
+    /// finish writing the current capture file
+    ///
+    /// dropping a recorder does the same, but can't report errors.
+    #[throws]
+    pub fn close(mut self) {
+        self.finish()?;
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            log::error!("{}", err);
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use std::path::PathBuf;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::marker::PhantomData;
 use std::time::Duration;
 
-use fehler::throws;
+use fehler::{throws, throw};
 
 use crate::Error;
+use crate::update::typed::SubscriptionSet;
 
 /// which pieces of information to subscribe to
 ///
@@ -80,9 +83,15 @@ impl From<&Subscription> for Subscription {
     }
 }
 
+/// the most digits after the decimal point CS:GO will output
+const MAX_PRECISION: u8 = 6;
+
 /// Builder struct for GSIConfig
+///
+/// `S` is the set of subscriptions that [typed listeners](struct.GSIServer.html#method.add_typed_listener)
+/// can rely on; see the [`typed`](update/typed/index.html) module.
 #[derive(Clone)]
-pub struct GSIConfigBuilder {
+pub struct GSIConfigBuilder<S = ()> {
     name: String,
     timeout: Option<Duration>,
     buffer: Option<Duration>,
@@ -93,13 +102,27 @@ pub struct GSIConfigBuilder {
     precision_position: Option<u8>,
     precision_vector: Option<u8>,
     subscriptions: HashSet<Subscription>,
+    typed: PhantomData<S>,
 }
 
 impl GSIConfigBuilder {
     /// Initialize the builder, with the given service name
-    pub fn new<S: Into<String>>(name: S) -> GSIConfigBuilder {
+    pub fn new<N: Into<String>>(name: N) -> GSIConfigBuilder {
+        GSIConfigBuilder::with_name(name.into())
+    }
+}
+
+impl<S: SubscriptionSet> GSIConfigBuilder<S> {
+    /// Initialize the builder, with the given service name, already subscribed to everything in `S`
+    pub fn typed<N: Into<String>>(name: N) -> Self {
+        let mut builder = Self::with_name(name.into());
+        builder.subscribe_multiple(S::subscriptions());
+        builder
+    }
+
+    fn with_name(name: String) -> Self {
         GSIConfigBuilder {
-            name: name.into(),
+            name,
             timeout: None,
             buffer: None,
             throttle: None,
@@ -108,53 +131,55 @@ impl GSIConfigBuilder {
             precision_time: None,
             precision_position: None,
             precision_vector: None,
-            subscriptions: HashSet::new()
+            subscriptions: HashSet::new(),
+            typed: PhantomData,
         }
     }
 
-    /// CS:GO's client timeout for requests (default is 1.1 seconds)
+    /// CS:GO's client timeout for requests (default is 1.1 seconds, must not be zero)
     pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
         self.timeout = Some(timeout);
         self
     }
 
-    /// minimum wait between sending updates (default is 0.1 seconds)
+    /// minimum wait between sending updates (default is 0.1 seconds, must not be zero)
     pub fn buffer(&mut self, buffer: Duration) -> &mut Self {
         self.buffer = Some(buffer);
         self
     }
 
-    /// minimum wait between response to one update and sending the next (default is 1.0 seconds)
+    /// minimum wait between response to one update and sending the next (default is 1.0 seconds,
+    /// must not be zero or longer than the heartbeat)
     pub fn throttle(&mut self, throttle: Duration) -> &mut Self {
         self.throttle = Some(throttle);
         self
     }
 
-    /// maximum time between updates (default is 60 seconds)
+    /// maximum time between updates (default is 60 seconds, must not be zero)
     pub fn heartbeat(&mut self, heartbeat: Duration) -> &mut Self {
         self.heartbeat = Some(heartbeat);
         self
     }
 
-    /// adds an authorization key/value pair (**not currently verified**)
+    /// adds an authorization key/value pair, which every update must include
     pub fn auth<S1: Into<String>, S2: Into<String>>(&mut self, key: S1, value: S2) -> &mut Self {
         self.auth.insert(key.into(), value.into());
         self
     }
 
-    /// digits after the decimal point in time values (default is 2)
+    /// digits after the decimal point in time values (default is 2, at most 6)
     pub fn precision_time(&mut self, precision: u8) -> &mut Self {
         self.precision_time = Some(precision);
         self
     }
 
-    /// digits after the decimal point in position values (default is 2)
+    /// digits after the decimal point in position values (default is 2, at most 6)
     pub fn precision_position(&mut self, precision: u8) -> &mut Self {
         self.precision_position = Some(precision);
         self
     }
 
-    /// digits after the decimal point in vector values (default is 2)
+    /// digits after the decimal point in vector values (default is 2, at most 6)
     pub fn precision_vector(&mut self, precision: u8) -> &mut Self {
         self.precision_vector = Some(precision);
         self
@@ -167,19 +192,20 @@ impl GSIConfigBuilder {
     }
 
     /// subscribe to several sets of update info
-    pub fn subscribe_multiple<I: IntoIterator<Item=S>, S: Into<Subscription>>(&mut self, subscriptions: I) -> &mut Self {
+    pub fn subscribe_multiple<I: IntoIterator<Item=T>, T: Into<Subscription>>(&mut self, subscriptions: I) -> &mut Self {
         self.subscriptions.extend(subscriptions.into_iter().map(|x| x.into()));
         self
     }
 
-    /// create the config object
-    pub fn build(&self) -> GSIConfig {
-        GSIConfig::from(self)
+    /// create the config object, checking that CS:GO will accept every value
+    #[throws]
+    pub fn try_build(&self) -> GSIConfig<S> {
+        GSIConfig::try_from(self)?
     }
 }
 
 /// Game State Integration configuration
-pub struct GSIConfig {
+pub struct GSIConfig<S = ()> {
     service_name: String,
     timeout: Duration,
     buffer: Duration,
@@ -190,42 +216,144 @@ pub struct GSIConfig {
     precision_position: u8,
     precision_vector: u8,
     subscriptions: HashSet<Subscription>,
+    typed: PhantomData<S>,
 }
 
-impl From<GSIConfigBuilder> for GSIConfig {
-    fn from(builder: GSIConfigBuilder) -> Self {
-        GSIConfig {
+impl<S: SubscriptionSet> TryFrom<GSIConfigBuilder<S>> for GSIConfig<S> {
+    type Error = Error;
+
+    #[throws]
+    fn try_from(builder: GSIConfigBuilder<S>) -> Self {
+        let config = GSIConfig {
             service_name: builder.name,
             timeout: builder.timeout.unwrap_or_else(|| Duration::from_secs_f64(1.1)),
             buffer: builder.buffer.unwrap_or_else(|| Duration::from_secs_f64(0.1)),
             throttle: builder.throttle.unwrap_or_else(|| Duration::from_secs_f64(1.0)),
-            heartbeat: builder.throttle.unwrap_or_else(|| Duration::from_secs(60)),
+            heartbeat: builder.heartbeat.unwrap_or_else(|| Duration::from_secs(60)),
             auth: builder.auth,
             precision_time: builder.precision_time.unwrap_or(2),
             precision_position: builder.precision_position.unwrap_or(2),
             precision_vector: builder.precision_vector.unwrap_or(2),
             subscriptions: builder.subscriptions,
-        }
+            typed: PhantomData,
+        };
+        config.validate()?;
+        config
     }
 }
 
-impl From<&GSIConfigBuilder> for GSIConfig {
-    fn from(builder: &GSIConfigBuilder) -> Self {
-        Self::from(builder.clone())
+impl<S: SubscriptionSet> TryFrom<&GSIConfigBuilder<S>> for GSIConfig<S> {
+    type Error = Error;
+
+    #[throws]
+    fn try_from(builder: &GSIConfigBuilder<S>) -> Self {
+        Self::try_from(builder.clone())?
     }
 }
 
-impl GSIConfig {
+impl<S> GSIConfig<S> {
     #[throws]
-    pub(crate) fn install_into<P: Into<PathBuf>>(&self, cfg_folder: P, port: u16) {
+    fn validate(&self) {
+        let zero = Duration::from_secs(0);
+        if self.timeout == zero {
+            throw!(Error::InvalidConfig { description: "timeout must not be zero" });
+        }
+        if self.buffer == zero {
+            throw!(Error::InvalidConfig { description: "buffer must not be zero" });
+        }
+        if self.throttle == zero {
+            throw!(Error::InvalidConfig { description: "throttle must not be zero" });
+        }
+        if self.heartbeat == zero {
+            throw!(Error::InvalidConfig { description: "heartbeat must not be zero" });
+        }
+        if self.throttle > self.heartbeat {
+            throw!(Error::InvalidConfig { description: "throttle must not be longer than heartbeat" });
+        }
+        if self.precision_time > MAX_PRECISION {
+            throw!(Error::InvalidConfig { description: "time precision must be at most 6 digits" });
+        }
+        if self.precision_position > MAX_PRECISION {
+            throw!(Error::InvalidConfig { description: "position precision must be at most 6 digits" });
+        }
+        if self.precision_vector > MAX_PRECISION {
+            throw!(Error::InvalidConfig { description: "vector precision must be at most 6 digits" });
+        }
+    }
+
+    /// the service name, as used in the installed config file's name
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    /// minimum wait between sending updates
+    pub fn buffer(&self) -> Duration {
+        self.buffer
+    }
+
+    /// minimum wait between response to one update and sending the next
+    pub fn throttle(&self) -> Duration {
+        self.throttle
+    }
+
+    /// maximum time between updates
+    pub fn heartbeat(&self) -> Duration {
+        self.heartbeat
+    }
+
+    /// authorization key/value pairs sent with every update
+    pub fn auth(&self) -> &HashMap<String, String> {
+        &self.auth
+    }
+
+    /// the sets of update info subscribed to
+    pub fn subscriptions(&self) -> &HashSet<Subscription> {
+        &self.subscriptions
+    }
+
+    /// add the given subscriptions, returning the ones that weren't already subscribed to
+    pub(crate) fn require<I: IntoIterator<Item=Subscription>>(&mut self, subscriptions: I) -> Vec<Subscription> {
+        subscriptions.into_iter()
+            .filter(|subscription| self.subscriptions.insert(*subscription))
+            .collect()
+    }
+
+    /// where a server at `base_uri` receives this config's updates when hosting it as a service
+    pub(crate) fn service_uri(&self, base_uri: &str) -> String {
+        format!("{}/svc/{}", base_uri.trim_end_matches('/'), percent_encode(&self.service_name))
+    }
+
+    pub(crate) fn cfg_path<P: Into<PathBuf>>(&self, cfg_folder: P) -> PathBuf {
         let mut cfg_path = cfg_folder.into();
-        cfg_path.push(&format!("gamestate_integration_{}.cfg", &self.service_name));
-        let config = config_file::ConfigFile::new(&self, port);
+        cfg_path.push(format!("gamestate_integration_{}.cfg", &self.service_name));
+        cfg_path
+    }
+
+    #[throws]
+    pub(crate) fn install_into<P: Into<PathBuf>>(&self, cfg_folder: P, uri: String) {
+        let cfg_path = self.cfg_path(cfg_folder);
+        let config = config_file::ConfigFile::new(self, uri);
         let config = vdf_serde::to_string(&config)
             .map_err(|err| Error::ConfigInstallError { description: "failed to serialize config for installation", cause: Some(Box::new(err)) })?;
         ::std::fs::write(cfg_path, config.as_bytes())
             .map_err(|err| Error::ConfigInstallError { description: "failed to write config file", cause: Some(Box::new(err)) })?;
     }
+
+    #[throws]
+    pub(crate) fn uninstall_from<P: Into<PathBuf>>(&self, cfg_folder: P) {
+        ::std::fs::remove_file(self.cfg_path(cfg_folder))
+            .map_err(|err| Error::ConfigInstallError { description: "failed to remove config file", cause: Some(Box::new(err)) })?;
+    }
+}
+
+/// escape everything but unreserved characters, so the name can be one segment of a URI path
+fn percent_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
 }
 
 mod config_file {
@@ -278,10 +406,10 @@ mod config_file {
     }
 
     impl ConfigFile {
-        pub fn new(config: &GSIConfig, port: u16) -> Self {
+        pub fn new<S>(config: &GSIConfig<S>, uri: String) -> Self {
             use super::Subscription;
             ConfigFile {
-                uri: format!("http://127.0.0.1:{}", port),
+                uri,
                 timeout: config.timeout.as_secs_f64(),
                 buffer: config.buffer.as_secs_f64(),
                 throttle: config.throttle.as_secs_f64(),
@@ -1,17 +1,86 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::path::PathBuf;
 
 use fehler::throws;
 
 /// any error caused by this library
 #[derive(Debug)]
 pub enum Error {
+    /// a configuration value that CS:GO wouldn't accept
+    InvalidConfig {
+        /// a textual description of the problem
+        description: &'static str,
+    },
     /// an error encountered when trying to install configuration
     ConfigInstallError {
         /// a textual description of the error
         description: &'static str,
         /// an upstream cause of the error
-        cause: Option<Box<dyn StdError>>,
+        cause: Option<Box<dyn StdError + Send + Sync>>,
+    },
+    /// an error encountered when trying to run the server
+    ServerError {
+        /// a textual description of the error
+        description: &'static str,
+        /// an upstream cause of the error
+        cause: Option<Box<dyn StdError + Send + Sync>>,
+    },
+    /// an update request's body couldn't be read
+    BodyRead {
+        /// an upstream cause of the error
+        cause: Box<dyn StdError + Send + Sync>,
+    },
+    /// an update request's body wasn't valid JSON
+    InvalidJson {
+        /// the raw request body
+        raw: String,
+        /// an upstream cause of the error
+        cause: serde_json::Error,
+    },
+    /// an update was valid JSON, but didn't match the expected structure
+    ///
+    /// serde doesn't say what type it expected in a structured way, so that's only in `cause`'s
+    /// message, like ``invalid type: string "five", expected u64``
+    UpdateSchema {
+        /// a JSON pointer to where in the update the mismatch was, e.g. `/player/weapons/weapon_3/type`
+        path: String,
+        /// the offending value, if there was one at that path
+        value: Option<serde_json::Value>,
+        /// the raw request body
+        raw: String,
+        /// an upstream cause of the error, whose message says what type was expected instead
+        cause: serde_json::Error,
+    },
+    /// an update's auth didn't match the configuration
+    Unauthorized {
+        /// the raw request body
+        raw: String,
+    },
+    /// the server stopped handing updates to its listeners
+    ListenerGone,
+    /// an error encountered when trying to record or replay a capture file
+    CaptureError {
+        /// a textual description of the error
+        description: &'static str,
+        /// an upstream cause of the error
+        cause: Option<Box<dyn StdError + Send + Sync>>,
+    },
+    /// an error encountered when trying to simulate a scripted match
+    SimulatorError {
+        /// a textual description of the error
+        description: &'static str,
+        /// an upstream cause of the error
+        cause: Option<Box<dyn StdError + Send + Sync>>,
+    },
+    /// a script failed to load, or one of its hooks failed
+    ScriptError {
+        /// the script's path
+        path: PathBuf,
+        /// the hook that failed, or `None` if the script failed to load
+        hook: Option<&'static str>,
+        /// what went wrong, as reported by the scripting engine
+        message: String,
     },
 }
 
@@ -19,9 +88,42 @@ impl fmt::Display for Error {
     #[throws(fmt::Error)]
     fn fmt(&self, f: &mut fmt::Formatter) {
         match self {
+            Error::InvalidConfig { description } => {
+                write!(f, "CS:GO GSI config error: {}", description)?;
+            }
             Error::ConfigInstallError { description, .. } => {
                 write!(f, "CS:GO GSI config install error: {}", description)?;
             }
+            Error::ServerError { description, .. } => {
+                write!(f, "CS:GO GSI server error: {}", description)?;
+            }
+            Error::BodyRead { cause } => {
+                write!(f, "CS:GO GSI update rejected: could not read body: {}", cause)?;
+            }
+            Error::InvalidJson { cause, .. } => {
+                write!(f, "CS:GO GSI update rejected: invalid JSON: {}", cause)?;
+            }
+            Error::UpdateSchema { path, cause, .. } => {
+                write!(f, "CS:GO GSI update rejected: unexpected data at {}: {}", path, cause)?;
+            }
+            Error::Unauthorized { .. } => {
+                write!(f, "CS:GO GSI update rejected: auth did not match")?;
+            }
+            Error::ListenerGone => {
+                write!(f, "CS:GO GSI update dropped: listeners are no longer running")?;
+            }
+            Error::CaptureError { description, .. } => {
+                write!(f, "CS:GO GSI capture error: {}", description)?;
+            }
+            Error::SimulatorError { description, .. } => {
+                write!(f, "CS:GO GSI simulator error: {}", description)?;
+            }
+            Error::ScriptError { path, hook: Some(hook), message } => {
+                write!(f, "CS:GO GSI script error in {} ({}): {}", path.display(), hook, message)?;
+            }
+            Error::ScriptError { path, hook: None, message } => {
+                write!(f, "CS:GO GSI script error in {}: {}", path.display(), message)?;
+            }
         }
     }
 }
@@ -29,7 +131,17 @@ impl fmt::Display for Error {
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
-            Error::ConfigInstallError { cause, .. } => cause.as_deref()
+            Error::InvalidConfig { .. } => None,
+            Error::ConfigInstallError { cause, .. } => cause.as_deref().map(|cause| cause as _),
+            Error::ServerError { cause, .. } => cause.as_deref().map(|cause| cause as _),
+            Error::BodyRead { cause } => Some(cause.as_ref()),
+            Error::InvalidJson { cause, .. } => Some(cause),
+            Error::UpdateSchema { cause, .. } => Some(cause),
+            Error::Unauthorized { .. } => None,
+            Error::ListenerGone => None,
+            Error::CaptureError { cause, .. } => cause.as_deref().map(|cause| cause as _),
+            Error::SimulatorError { cause, .. } => cause.as_deref().map(|cause| cause as _),
+            Error::ScriptError { .. } => None,
         }
     }
 }
@@ -0,0 +1,153 @@
+//! higher-level events, derived by comparing consecutive updates
+
+use serde::{Serialize, Deserialize};
+
+use crate::update::{Team, Update, map, round};
+
+/// something that happened between one update and the next
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// the game started sending updates, either for the first time or after disconnecting
+    GameConnected,
+    /// the game hasn't sent an update within its heartbeat, plus a grace period
+    GameDisconnected,
+    /// the game's timestamp went backwards, so it has probably been restarted
+    GameRestarted,
+    /// a different map was loaded
+    MapChanged {
+        /// map name
+        name: String,
+    },
+    /// the map phase changed (warmup, live, halftime, game over)
+    MapPhaseChanged {
+        /// the new phase
+        phase: map::Phase,
+    },
+    /// freeze time ended and a round went live
+    RoundStarted {
+        /// the round number, starting from 1
+        round: u64,
+    },
+    /// a round ended
+    RoundEnded {
+        /// the round number, starting from 1
+        round: u64,
+        /// which team won, if known
+        winner: Option<Team>,
+    },
+    /// the bomb was planted
+    BombPlanted,
+    /// the bomb was defused
+    BombDefused,
+    /// the bomb exploded
+    BombExploded,
+    /// the player died
+    PlayerDied {
+        /// the player's steam ID
+        steam_id: String,
+    },
+    /// the player got a kill
+    PlayerGotKill {
+        /// the player's steam ID
+        steam_id: String,
+        /// kills this round, including this one
+        round_kills: i64,
+    },
+}
+
+impl Event {
+    /// which section of an update this event came from: `map`, `player`, `provider` or `round`
+    pub fn topic(&self) -> &'static str {
+        match self {
+            Event::GameConnected | Event::GameDisconnected | Event::GameRestarted => "provider",
+            Event::MapChanged { .. } | Event::MapPhaseChanged { .. } => "map",
+            Event::RoundStarted { .. } | Event::RoundEnded { .. } => "round",
+            Event::BombPlanted | Event::BombDefused | Event::BombExploded => "round",
+            Event::PlayerDied { .. } | Event::PlayerGotKill { .. } => "player",
+        }
+    }
+}
+
+/// remembers the previous update, so each new one can be turned into events
+#[derive(Default)]
+pub struct EventDetector {
+    previous: Option<Update>,
+}
+
+impl EventDetector {
+    /// create a detector that hasn't seen any updates yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// forget the previous update, e.g. because the game restarted
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// the events that happened since the last update this detector saw
+    pub fn detect(&mut self, update: &Update) -> Vec<Event> {
+        let events = diff(self.previous.as_ref(), update);
+        self.previous = Some(update.clone());
+        events
+    }
+}
+
+/// the events that happened between two updates
+///
+/// if there's no previous update, only the map being loaded is reported.
+pub fn diff(previous: Option<&Update>, current: &Update) -> Vec<Event> {
+    let mut events = vec![];
+
+    let previous_map = previous.and_then(|update| update.map.as_ref());
+    if let Some(map) = &current.map {
+        match previous_map {
+            Some(previous_map) if previous_map.name == map.name => {
+                if previous_map.phase != map.phase {
+                    events.push(Event::MapPhaseChanged { phase: map.phase });
+                }
+            }
+            _ => events.push(Event::MapChanged { name: map.name.clone() }),
+        }
+    }
+
+    // a round ending is reported after everything else that happened in the same update
+    let mut round_ended = None;
+    let previous_round = previous.and_then(|update| update.round.as_ref());
+    if let (Some(previous_round), Some(round)) = (previous_round, &current.round) {
+        let map_round = current.map.as_ref().map_or(0, |map| map.round);
+        if previous_round.phase != round.phase {
+            match round.phase {
+                round::Phase::Live => events.push(Event::RoundStarted { round: map_round + 1 }),
+                round::Phase::Over => round_ended = Some(Event::RoundEnded { round: map_round, winner: round.win_team }),
+                round::Phase::FreezeTime => {}
+            }
+        }
+        if previous_round.bomb != round.bomb {
+            match round.bomb {
+                Some(round::BombState::Planted) => events.push(Event::BombPlanted),
+                Some(round::BombState::Defused) => events.push(Event::BombDefused),
+                Some(round::BombState::Exploded) => events.push(Event::BombExploded),
+                None => {}
+            }
+        }
+    }
+
+    let previous_player = previous.and_then(|update| update.player.as_ref());
+    if let (Some(previous_player), Some(player)) = (previous_player, &current.player) {
+        if previous_player.steam_id == player.steam_id {
+            if let (Some(previous_state), Some(state)) = (&previous_player.state, &player.state) {
+                if previous_state.health > 0 && state.health == 0 {
+                    events.push(Event::PlayerDied { steam_id: player.steam_id.clone() });
+                }
+                if state.round_kills > previous_state.round_kills {
+                    events.push(Event::PlayerGotKill { steam_id: player.steam_id.clone(), round_kills: state.round_kills });
+                }
+            }
+        }
+    }
+
+    events.extend(round_ended);
+    events
+}
@@ -1,11 +1,14 @@
 use std::path::PathBuf;
 use std::collections::HashMap;
 
-use fehler::{throws, throw};
+use fehler::throws;
+#[cfg(target_os = "windows")]
+use fehler::throw;
 use serde::Deserialize;
 
 use crate::Error;
 
+/// find the `libraryfolders.vdf` file that lists every Steam library folder
 #[cfg(target_os = "windows")]
 #[throws]
 pub fn get_library_folders() -> PathBuf {
@@ -26,6 +29,7 @@ pub fn get_library_folders() -> PathBuf {
     }
 }
 
+/// find the `libraryfolders.vdf` file that lists every Steam library folder
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 #[throws]
 pub fn get_library_folders() -> PathBuf {
@@ -43,6 +47,7 @@ pub fn get_library_folders() -> PathBuf {
 #[serde(rename = "libraryfolders")]
 struct LibraryFolders(HashMap<String, String>);
 
+/// find the `/path/to/csgo/cfg/` folder by looking through every Steam library folder
 #[throws]
 pub fn discover_cfg_folder() -> PathBuf {
     use std::iter;
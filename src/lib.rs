@@ -13,7 +13,8 @@
 //! async fn main() {
 //!     let config = GSIConfigBuilder::new("csgo-gsi Example")
 //!         .subscribe_multiple(Subscription::UNRESTRICTED)
-//!         .build();
+//!         .try_build()
+//!         .expect("config wasn't valid");
 //!
 //!     let mut server = GSIServer::new(config, 31337);
 //!     server.add_listener(|update| println!("Got an update {:#?}", update));
@@ -30,13 +31,22 @@
 #[macro_use]
 extern crate gotham_derive;
 
+pub mod capture;
 mod config;
 mod error;
+pub mod events;
 mod install_dir;
+pub mod replay;
+#[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+pub mod scripting;
 mod server;
+pub mod simulator;
+pub mod team;
 pub mod update;
 
 pub use config::{Subscription, GSIConfigBuilder, GSIConfig};
 pub use error::Error;
-pub use server::GSIServer;
+pub use install_dir::{discover_cfg_folder, get_library_folders};
+pub use server::{GSIServer, Service, ShutdownHandle};
 pub use update::Update;
+pub use update::typed::TypedUpdate;
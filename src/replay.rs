@@ -0,0 +1,182 @@
+//! replaying recorded capture files
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use fehler::{throws, throw};
+use flate2::read::GzDecoder;
+use gotham::hyper::{Body, Client, Request, header};
+use serde::Deserialize;
+
+use crate::Error;
+use crate::capture::CapturedUpdate;
+use crate::update::Update;
+
+#[throws]
+fn open_capture<P: AsRef<Path>>(path: P) -> Box<dyn Read> {
+    let mut file = File::open(path)
+        .map_err(|err| Error::CaptureError { description: "failed to open capture file", cause: Some(Box::new(err)) })?;
+    let mut magic = [0u8; 2];
+    let is_gzip = file.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    file.seek(SeekFrom::Start(0))
+        .map_err(|err| Error::CaptureError { description: "failed to read capture file", cause: Some(Box::new(err)) })?;
+    if is_gzip {
+        Box::new(GzDecoder::new(file)) as Box<dyn Read>
+    } else {
+        Box::new(file)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawCapture {
+    raw: String,
+}
+
+/// re-parse the raw body of every update in a capture file against the current schema,
+/// returning the (1-based) line number and parse error for each one that no longer parses
+#[throws]
+pub fn audit<P: AsRef<Path>>(path: P) -> Vec<(usize, Error)> {
+    let mut failures = vec![];
+    for (index, line) in BufReader::new(open_capture(path)?).lines().enumerate() {
+        let line = line
+            .map_err(|err| Error::CaptureError { description: "failed to read capture file", cause: Some(Box::new(err)) })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let captured = serde_json::from_str::<RawCapture>(&line)
+            .map_err(|err| Error::CaptureError { description: "failed to parse captured update", cause: Some(Box::new(err)) })?;
+        if let Err(err) = Update::from_json(&captured.raw) {
+            failures.push((index + 1, err));
+        }
+    }
+    failures
+}
+
+/// how quickly to replay a capture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    /// wait between updates exactly as long as the game did
+    RealTime,
+    /// play back this many times faster than real time
+    Accelerated(f64),
+    /// don't wait between updates at all
+    AsFastAsPossible,
+}
+
+impl Speed {
+    fn delay(self, previous: &CapturedUpdate, next: &CapturedUpdate) -> Option<Duration> {
+        let gap = Duration::from_millis(next.received_at.saturating_sub(previous.received_at));
+        match self {
+            Speed::RealTime => Some(gap),
+            Speed::Accelerated(factor) if factor > 0.0 => Some(gap.div_f64(factor)),
+            Speed::Accelerated(_) | Speed::AsFastAsPossible => None,
+        }
+    }
+}
+
+/// plays back updates from a capture file written by a [`Recorder`](../capture/struct.Recorder.html)
+pub struct Replayer {
+    captures: Vec<CapturedUpdate>,
+    speed: Speed,
+    start: usize,
+}
+
+impl Replayer {
+    /// load every update from the given capture file (gzip-compressed or not)
+    #[throws]
+    pub fn open<P: AsRef<Path>>(path: P) -> Self {
+        Self::from_reader(open_capture(path)?)?
+    }
+
+    /// load every update from the given newline-delimited JSON
+    #[throws]
+    pub fn from_reader<R: Read>(reader: R) -> Self {
+        let mut captures = vec![];
+        for line in BufReader::new(reader).lines() {
+            let line = line
+                .map_err(|err| Error::CaptureError { description: "failed to read capture file", cause: Some(Box::new(err)) })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let captured = serde_json::from_str(&line)
+                .map_err(|err| Error::CaptureError { description: "failed to parse captured update", cause: Some(Box::new(err)) })?;
+            captures.push(captured);
+        }
+        Self::from_captures(captures)
+    }
+
+    /// replay the given updates
+    pub fn from_captures(captures: Vec<CapturedUpdate>) -> Self {
+        Self {
+            captures,
+            speed: Speed::AsFastAsPossible,
+            start: 0,
+        }
+    }
+
+    /// how quickly to replay (default is as fast as possible)
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// skip ahead to the first update whose map round number is at least `round`
+    #[throws]
+    pub fn seek_to_round(mut self, round: u64) -> Self {
+        let start = self.captures.iter()
+            .position(|captured| captured.update.map.as_ref().is_some_and(|map| map.round >= round));
+        match start {
+            Some(start) => self.start = start,
+            None => throw!(Error::CaptureError { description: "capture never reaches the requested round", cause: None }),
+        }
+        self
+    }
+
+    /// the updates that will be replayed
+    pub fn captures(&self) -> &[CapturedUpdate] {
+        &self.captures[self.start..]
+    }
+
+    /// feed each captured update directly into the given listener (blocks until done)
+    pub fn replay_captures_into<F: FnMut(&CapturedUpdate)>(&self, mut listener: F) {
+        let mut previous: Option<&CapturedUpdate> = None;
+        for captured in self.captures() {
+            if let Some(delay) = previous.and_then(|previous| self.speed.delay(previous, captured)) {
+                thread::sleep(delay);
+            }
+            listener(captured);
+            previous = Some(captured);
+        }
+    }
+
+    /// feed each update directly into the given listener (blocks until done)
+    pub fn replay_into<F: FnMut(&Update)>(&self, mut listener: F) {
+        self.replay_captures_into(|captured| listener(&captured.update));
+    }
+
+    /// POST each raw update to a running [`GSIServer`](../struct.GSIServer.html) at the given URI,
+    /// e.g. `http://127.0.0.1:31337`
+    #[throws]
+    pub async fn replay_to(&self, uri: &str) {
+        let client = Client::new();
+        let mut previous: Option<&CapturedUpdate> = None;
+        for captured in self.captures() {
+            if let Some(delay) = previous.and_then(|previous| self.speed.delay(previous, captured)) {
+                tokio::time::delay_for(delay).await;
+            }
+            let request = Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(captured.raw.clone()))
+                .map_err(|err| Error::CaptureError { description: "failed to build replay request", cause: Some(Box::new(err)) })?;
+            let response = client.request(request).await
+                .map_err(|err| Error::CaptureError { description: "failed to send replayed update", cause: Some(Box::new(err)) })?;
+            if !response.status().is_success() {
+                throw!(Error::CaptureError { description: "server rejected replayed update", cause: None });
+            }
+            previous = Some(captured);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! the Lua script host
+
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use fehler::throws;
+use mlua::{Function, HookTriggers, IntoLuaMulti, Lua, LuaOptions, LuaSerdeExt, MultiValue, StdLib, Value};
+use mlua::serde::ser::Options as SerializeOptions;
+
+use crate::Error;
+use crate::events::Event;
+use crate::update::{Update, load_lua_package, lua_name};
+use super::ScriptHooks;
+
+/// how much a Lua script can do before it's stopped and disabled
+#[derive(Clone, Debug)]
+pub struct LuaLimits {
+    /// memory the whole script can use, in bytes, or 0 for no limit (default is 16 MiB)
+    pub max_memory: usize,
+    /// wall-clock time for each call (default is 100 milliseconds)
+    pub time_budget: Duration,
+}
+
+impl Default for LuaLimits {
+    fn default() -> Self {
+        Self {
+            max_memory: 16 * 1024 * 1024,
+            time_budget: Duration::from_millis(100),
+        }
+    }
+}
+
+/// how often, in Lua instructions, a running script checks whether it's out of time
+const DEADLINE_CHECK_INTERVAL: u32 = 1000;
+
+/// when the current call has to be finished by, checked as the script runs
+#[derive(Default)]
+struct Deadline {
+    at: Cell<Option<Instant>>,
+    /// set once the deadline's passed, so the script keeps failing even if it catches the error
+    passed: Cell<bool>,
+}
+
+impl Deadline {
+    fn check(&self) -> mlua::Result<()> {
+        if !self.passed.get() && self.at.get().is_some_and(|at| Instant::now() >= at) {
+            self.passed.set(true);
+        }
+        match self.passed.get() {
+            true => Err(mlua::Error::RuntimeError("time budget exceeded".to_string())),
+            false => Ok(()),
+        }
+    }
+}
+
+/// replaces `load` with one that refuses bytecode, and removes `string.dump`, which makes it
+const SOURCE_ONLY_LOAD: &str = r#"
+local load = load
+_G.load = function(chunk, name, _, ...)
+    return load(chunk, name, "t", ...)
+end
+string.dump = nil
+"#;
+
+type Setup = Box<dyn Fn(&Lua) -> mlua::Result<()>>;
+
+struct Script {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    lua: Lua,
+    disabled: bool,
+}
+
+impl Script {
+    fn error(&self, hook: Option<&'static str>, error: &mlua::Error) -> Error {
+        let message = match self.disabled {
+            true => format!("{} (disabled until it changes)", error),
+            false => error.to_string(),
+        };
+        Error::ScriptError { path: self.path.clone(), hook, message }
+    }
+}
+
+/// loads Lua scripts and calls their hooks
+///
+/// each script gets its own Lua state, with only the `table`, `string`, `math`, `utf8` and
+/// `coroutine` libraries, so scripts can't touch files or run programs, and `load` only accepts
+/// source code. each call is held to
+/// [limits](struct.LuaLimits.html) on memory and time, and a script that goes over them is
+/// disabled, and the error is reported, until the script changes.
+///
+/// updates are passed as userdata with the same getters as in [Rhai](struct.ScriptHost.html), and
+/// scripts can use the same [helpers](index.html#helpers) and keep things in the `state` table.
+pub struct LuaScriptHost {
+    scripts: Vec<Script>,
+    setup: Vec<Setup>,
+    previous: Option<Update>,
+    limits: LuaLimits,
+    deadline: Rc<Deadline>,
+}
+
+impl Default for LuaScriptHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LuaScriptHost {
+    /// create a host with no scripts loaded and the default limits
+    pub fn new() -> Self {
+        Self::with_limits(LuaLimits::default())
+    }
+
+    /// create a host with no scripts loaded and the given limits
+    pub fn with_limits(limits: LuaLimits) -> Self {
+        Self {
+            scripts: vec![],
+            setup: vec![],
+            previous: None,
+            limits,
+            deadline: Rc::new(Deadline::default()),
+        }
+    }
+
+    /// run the given function on each script's Lua state before the script is loaded, e.g. to
+    /// add more globals
+    ///
+    /// this only affects scripts loaded (or reloaded) afterwards.
+    pub fn on_setup<F: 'static + Fn(&Lua) -> mlua::Result<()>>(&mut self, setup: F) {
+        self.setup.push(Box::new(setup));
+    }
+
+    /// run a call under the time budget, failing it if it went over, even if the script caught
+    /// the error that stopped it
+    fn with_deadline<T>(&self, f: impl FnOnce() -> mlua::Result<T>) -> mlua::Result<T> {
+        self.deadline.passed.set(false);
+        self.deadline.at.set(Some(Instant::now() + self.limits.time_budget));
+        let result = f();
+        let passed = self.deadline.check();
+        self.deadline.at.set(None);
+        self.deadline.passed.set(false);
+        result.and_then(|result| passed.map(|_| result))
+    }
+
+    /// whether an error means a script went over its limits, rather than just failing
+    fn over_limits(&self, error: &mlua::Error, started: Instant) -> bool {
+        match error {
+            mlua::Error::MemoryError(_) | mlua::Error::StackError => true,
+            mlua::Error::CallbackError { cause, .. } => self.over_limits(cause, started),
+            _ => started.elapsed() >= self.limits.time_budget,
+        }
+    }
+
+    /// set up a Lua state for a script and run its top level
+    fn compile(&self, path: &Path, state: serde_json::Value) -> mlua::Result<Lua> {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE;
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        let globals = lua.globals();
+        // the base library is always loaded, but these two read files
+        globals.raw_remove("dofile")?;
+        globals.raw_remove("loadfile")?;
+        // and crafted bytecode can corrupt memory, so only source can be loaded
+        lua.load(SOURCE_ONLY_LOAD).set_name("sandbox").exec()?;
+        load_lua_package(&lua)?;
+        globals.set("state", lua.to_value(&state)?)?;
+        for setup in &self.setup {
+            setup(&lua)?;
+        }
+        drop(globals);
+        let deadline = Rc::clone(&self.deadline);
+        // checking on calls too means `pcall` can't be used to keep going once time's up
+        let triggers = HookTriggers::new().on_calls().every_nth_instruction(DEADLINE_CHECK_INTERVAL);
+        lua.set_hook(triggers, move |_, _| deadline.check());
+        if self.limits.max_memory > 0 {
+            lua.set_memory_limit(self.limits.max_memory)?;
+        }
+        let source = fs::read(path).map_err(mlua::Error::external)?;
+        self.with_deadline(|| lua.load(&source).set_name(path.display().to_string()).exec())?;
+        Ok(lua)
+    }
+
+    /// load the script at the given path
+    ///
+    /// it's reloaded whenever it changes. if it doesn't compile, or its top level fails, it isn't
+    /// loaded at all.
+    #[throws]
+    pub fn load<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let modified = super::modified(&path);
+        let lua = self.compile(&path, serde_json::Value::Object(Default::default()))
+            .map_err(|error| Error::ScriptError { path: path.clone(), hook: None, message: error.to_string() })?;
+        self.scripts.push(Script { path, modified, lua, disabled: false });
+    }
+
+    /// the scripts that went over their limits, and won't run again until they change
+    pub fn disabled_scripts(&self) -> impl Iterator<Item=&Path> {
+        self.scripts.iter()
+            .filter(|script| script.disabled)
+            .map(|script| script.path.as_path())
+    }
+
+    /// reload any scripts that have changed since they were last loaded
+    ///
+    /// this happens automatically before each update. if a changed script doesn't compile, the
+    /// old version keeps running (or stays disabled).
+    pub fn reload(&mut self) -> Vec<Error> {
+        let mut errors = vec![];
+        for index in 0..self.scripts.len() {
+            let script = &self.scripts[index];
+            let modified = super::modified(&script.path);
+            if modified == script.modified {
+                continue;
+            }
+            let (state, reset) = match script.lua.globals().get::<_, Value>("state").and_then(|state| script.lua.from_value(state)) {
+                Ok(state) => (state, None),
+                Err(error) => (serde_json::Value::Object(Default::default()), Some(error)),
+            };
+            let result = self.compile(&script.path, state);
+            let script = &mut self.scripts[index];
+            script.modified = modified;
+            match result {
+                Ok(lua) => {
+                    log::info!("reloaded script {}", script.path.display());
+                    script.lua = lua;
+                    script.disabled = false;
+                    if let Some(error) = reset {
+                        let message = format!("state couldn't be kept, so it was reset: {}", error);
+                        errors.push(Error::ScriptError { path: script.path.clone(), hook: None, message });
+                    }
+                }
+                Err(error) => errors.push(Error::ScriptError { path: script.path.clone(), hook: None, message: error.to_string() }),
+            }
+        }
+        errors
+    }
+
+    /// call the given hook in every script that defines it, with the arguments made for that script
+    fn call<A>(&mut self, hook: &'static str, args: A) -> Vec<Error>
+    where A: for<'lua> Fn(&'lua Lua) -> mlua::Result<MultiValue<'lua>> {
+        let mut errors = vec![];
+        for index in 0..self.scripts.len() {
+            let script = &self.scripts[index];
+            if script.disabled {
+                continue;
+            }
+            let started = Instant::now();
+            let result = script.lua.globals().get::<_, Option<Function>>(hook).and_then(|function| match function {
+                Some(function) => self.with_deadline(|| function.call::<_, ()>(args(&script.lua)?)),
+                None => Ok(()),
+            });
+            if let Err(error) = result {
+                let over_limits = self.over_limits(&error, started);
+                let script = &mut self.scripts[index];
+                script.disabled = over_limits;
+                errors.push(script.error(Some(hook), &error));
+            }
+        }
+        errors
+    }
+
+    /// reload any changed scripts, then call their `on_update` hooks
+    pub fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        let mut errors = self.reload();
+        let previous = self.previous.replace(update.clone());
+        errors.extend(self.call("on_update", |lua| (update.clone(), previous.clone()).into_lua_multi(lua)));
+        errors
+    }
+
+    /// call the `on_event` hook, and the hook for the given event, if scripts define them
+    pub fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        let options = SerializeOptions::new().serialize_none_to_null(false);
+        let mut errors = self.call("on_event", |lua| lua.to_value_with(event, options)?.into_lua_multi(lua));
+        errors.extend(self.call(super::event_hook(event), |lua| {
+            match event {
+                Event::GameConnected | Event::GameDisconnected | Event::GameRestarted => ().into_lua_multi(lua),
+                Event::MapChanged { name } => name.as_str().into_lua_multi(lua),
+                Event::MapPhaseChanged { phase } => lua_name(phase).into_lua_multi(lua),
+                Event::RoundStarted { round } => round.into_lua_multi(lua),
+                Event::RoundEnded { round, winner } => (*round, winner.as_ref().map(lua_name)).into_lua_multi(lua),
+                Event::BombPlanted | Event::BombDefused | Event::BombExploded => ().into_lua_multi(lua),
+                Event::PlayerDied { steam_id } => steam_id.as_str().into_lua_multi(lua),
+                Event::PlayerGotKill { steam_id, round_kills } => (steam_id.as_str(), *round_kills).into_lua_multi(lua),
+            }
+        }));
+        errors
+    }
+}
+
+impl ScriptHooks for LuaScriptHost {
+    fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        LuaScriptHost::handle_update(self, update)
+    }
+
+    fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        LuaScriptHost::handle_event(self, event)
+    }
+}
@@ -0,0 +1,117 @@
+//! scripts that react to updates and events, reloaded whenever they change on disk
+//!
+//! scripts can be written in [Rhai](struct.ScriptHost.html), with the `rhai` feature, or
+//! [Lua](struct.LuaScriptHost.html), with the `lua` feature. either way, each script can define
+//! any of these hooks, which are called when the matching thing happens:
+//!
+//! | hook | arguments |
+//! |------|-----------|
+//! | `on_update` | the [update](../update/struct.Update.html), and optionally the previous one or `()`/`nil` |
+//! | `on_event` | any [event](../events/enum.Event.html), as an object map/table like `{type: "round_ended", round: 3, winner: "CT"}` |
+//! | `on_game_connected`, `on_game_disconnected`, `on_game_restarted` | |
+//! | `on_map_changed` | the map name |
+//! | `on_map_phase_changed` | the new phase |
+//! | `on_round_start` | the round number |
+//! | `on_round_end` | the round number, and the winning team or `()`/`nil` |
+//! | `on_bomb_planted`, `on_bomb_defused`, `on_bomb_exploded` | |
+//! | `on_player_died` | the player's steam ID |
+//! | `on_player_kill` | the player's steam ID, and their kills this round |
+//!
+//! variables declared at the top level of a script are kept between calls, so hooks can use
+//! them to remember things. they start over when the script is reloaded, except for `state`, an
+//! object map/table that's kept for as long as the host is running. in Lua, `state` is copied
+//! into the reloaded script, so it can only hold what JSON can, and it's reset (and the reset
+//! reported) if it holds anything else, like a function.
+//!
+//! # helpers
+//!
+//! besides getters for every part of an update, scripts can use these:
+//!
+//! - `is_alive(player)`, also for `player.state`
+//! - `active_weapon(player)`: the weapon being held, or `()`/`nil`
+//! - `has_weapon_type(player, "Rifle")`: whether any weapon is of the given
+//!   [type](../update/player/enum.WeaponType.html)
+//! - `team_of(update)`, also for `update.player`: the player's team, or `()`/`nil`
+//! - `parsed_round_wins(map)`: a list like `[{round: 1, team: T, reason: "elimination"}, ...]`
+//!
+//! with the `wasm` feature, [WebAssembly plugins](struct.WasmPluginHost.html) can react to updates
+//! and events too, with their own, lower-level interface.
+//!
+//! ```no_run
+//! # #[cfg(feature = "rhai")]
+//! # async fn run() -> Result<(), csgo_gsi::Error> {
+//! use csgo_gsi::{GSIConfigBuilder, GSIServer, Subscription};
+//! use csgo_gsi::scripting::ScriptHost;
+//!
+//! let config = GSIConfigBuilder::new("csgo-gsi Example")
+//!     .subscribe_multiple(Subscription::UNRESTRICTED)
+//!     .try_build()?;
+//! let mut host = ScriptHost::new();
+//! host.load("scripts/announcer.rhai")?;
+//! let mut server = GSIServer::new(config, 31337);
+//! server.add_script_host(host);
+//! server.run().await
+//! # }
+//! ```
+
+use std::fs;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+use crate::Error;
+use crate::events::Event;
+use crate::update::Update;
+
+#[cfg(feature = "lua")]
+mod lua_host;
+#[cfg(feature = "lua")]
+pub use lua_host::{LuaLimits, LuaScriptHost};
+
+#[cfg(feature = "rhai")]
+mod rhai_host;
+#[cfg(feature = "rhai")]
+pub use rhai_host::{ScriptHost, ScriptLimits};
+
+#[cfg(feature = "wasm")]
+mod wasm_host;
+#[cfg(feature = "wasm")]
+pub use wasm_host::{PluginEvent, WasmLimits, WasmPluginHost};
+
+/// something that calls scripts' hooks, which a [`GSIServer`](../struct.GSIServer.html) can run
+pub trait ScriptHooks {
+    /// reload any changed scripts, then call their `on_update` hooks
+    fn handle_update(&mut self, update: &Update) -> Vec<Error>;
+    /// call the `on_event` hook, and the hook for the given event, if scripts define them
+    fn handle_event(&mut self, event: &Event) -> Vec<Error>;
+    /// when the next timer scripts have set goes off, if they've set any
+    fn next_timer(&self) -> Option<Instant> {
+        None
+    }
+    /// call the hooks for any timers that have gone off
+    fn fire_timers(&mut self) -> Vec<Error> {
+        vec![]
+    }
+}
+
+/// the name of the hook for the given event
+#[cfg(any(feature = "lua", feature = "rhai"))]
+fn event_hook(event: &Event) -> &'static str {
+    match event {
+        Event::GameConnected => "on_game_connected",
+        Event::GameDisconnected => "on_game_disconnected",
+        Event::GameRestarted => "on_game_restarted",
+        Event::MapChanged { .. } => "on_map_changed",
+        Event::MapPhaseChanged { .. } => "on_map_phase_changed",
+        Event::RoundStarted { .. } => "on_round_start",
+        Event::RoundEnded { .. } => "on_round_end",
+        Event::BombPlanted => "on_bomb_planted",
+        Event::BombDefused => "on_bomb_defused",
+        Event::BombExploded => "on_bomb_exploded",
+        Event::PlayerDied { .. } => "on_player_died",
+        Event::PlayerGotKill { .. } => "on_player_kill",
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
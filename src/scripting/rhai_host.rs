@@ -0,0 +1,269 @@
+//! the Rhai script host
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use fehler::throws;
+use rhai::{AST, Dynamic, Engine, EvalAltResult, ImmutableString, Map as RhaiMap, Module, Scope, packages::Package};
+use rhai::module_resolvers::FileModuleResolver;
+
+use crate::Error;
+use crate::events::Event;
+use crate::update::{CSGOPackage, Update};
+use super::ScriptHooks;
+
+/// how much a script can do in each call before it's stopped and disabled
+#[derive(Clone, Debug)]
+pub struct ScriptLimits {
+    /// Rhai operations (roughly, expressions evaluated), or 0 for no limit (default is 1,000,000)
+    pub max_operations: u64,
+    /// nested function calls (default is 32)
+    pub max_call_levels: usize,
+    /// length of any string, in bytes, or 0 for no limit (default is 65,536)
+    pub max_string_size: usize,
+    /// length of any array, or 0 for no limit (default is 10,000)
+    pub max_array_size: usize,
+    /// number of entries in any object map, or 0 for no limit (default is 10,000)
+    pub max_map_size: usize,
+    /// wall-clock time (default is 100 milliseconds)
+    pub time_budget: Duration,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 1_000_000,
+            max_call_levels: 32,
+            max_string_size: 65_536,
+            max_array_size: 10_000,
+            max_map_size: 10_000,
+            time_budget: Duration::from_millis(100),
+        }
+    }
+}
+
+/// whether an error means a script went over its limits, rather than just failing
+fn over_limits(error: &EvalAltResult) -> bool {
+    match error {
+        EvalAltResult::ErrorInFunctionCall(_, inner, _) => over_limits(inner),
+        EvalAltResult::ErrorTooManyOperations(_) |
+        EvalAltResult::ErrorStackOverflow(_) |
+        EvalAltResult::ErrorDataTooLarge(..) |
+        EvalAltResult::ErrorTerminated(_) => true,
+        _ => false,
+    }
+}
+
+struct Script {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ast: AST,
+    scope: Scope<'static>,
+    disabled: bool,
+}
+
+impl Script {
+    fn error(&self, hook: Option<&'static str>, error: &EvalAltResult) -> Error {
+        let message = match self.disabled {
+            true => format!("{} (disabled until it changes)", error),
+            false => error.to_string(),
+        };
+        Error::ScriptError { path: self.path.clone(), hook, message }
+    }
+
+    fn has_hook(&self, hook: &str, args: usize) -> bool {
+        AsRef::<Module>::as_ref(&self.ast).iter_script_fn().any(|function| function.name == hook && function.params.len() == args)
+    }
+}
+
+/// loads Rhai scripts and calls their hooks
+///
+/// scripts are sandboxed: they can't import other files, and each call is held to
+/// [limits](struct.ScriptLimits.html) on how much work it does and how long it takes. a script
+/// that goes over them is disabled, and the error is reported, until the script changes.
+///
+/// besides getters for every part of an update, scripts can use the [helpers](index.html#helpers),
+/// and keep things in the `state` object map.
+pub struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+    previous: Option<Update>,
+    time_budget: Duration,
+    /// when the current call has to be finished by, checked as the script runs
+    deadline: Rc<Cell<Option<Instant>>>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptHost {
+    /// create a host with no scripts loaded and the default limits, whose engine knows about updates
+    pub fn new() -> Self {
+        Self::with_limits(ScriptLimits::default())
+    }
+
+    /// create a host with no scripts loaded and the given limits
+    pub fn with_limits(limits: ScriptLimits) -> Self {
+        let mut engine = Engine::new();
+        engine.load_package(CSGOPackage::new().get());
+        // debug builds of Rhai default to much lower limits, which long `else if` chains run into
+        engine.set_max_expr_depths(128, 32);
+        engine.set_max_operations(limits.max_operations);
+        engine.set_max_call_levels(limits.max_call_levels);
+        engine.set_max_string_size(limits.max_string_size);
+        engine.set_max_array_size(limits.max_array_size);
+        engine.set_max_map_size(limits.max_map_size);
+        engine.set_module_resolver(None::<FileModuleResolver>);
+        let deadline = Rc::new(Cell::new(None));
+        let progress_deadline = Rc::clone(&deadline);
+        engine.on_progress(move |_| progress_deadline.get().is_none_or(|deadline| Instant::now() < deadline));
+        Self {
+            engine,
+            scripts: vec![],
+            previous: None,
+            time_budget: limits.time_budget,
+            deadline,
+        }
+    }
+
+    /// the Rhai engine scripts are run with, e.g. to register more functions before loading any
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    /// compile a script and run its top level, to set up its variables
+    #[throws]
+    fn compile(&self, path: &Path, state: RhaiMap) -> (AST, Scope<'static>) {
+        let to_error = |error: &EvalAltResult| Error::ScriptError { path: path.to_path_buf(), hook: None, message: error.to_string() };
+        let ast = self.engine.compile_file(path.to_path_buf()).map_err(|error| to_error(&error))?;
+        let mut scope = Scope::new();
+        scope.push("state", state);
+        self.deadline.set(Some(Instant::now() + self.time_budget));
+        let result = self.engine.consume_ast_with_scope(&mut scope, &ast);
+        self.deadline.set(None);
+        result.map_err(|error| to_error(&error))?;
+        (ast, scope)
+    }
+
+    /// load the script at the given path
+    ///
+    /// it's reloaded whenever it changes. if it doesn't compile, or its top level fails, it isn't
+    /// loaded at all.
+    #[throws]
+    pub fn load<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let modified = super::modified(&path);
+        let (ast, scope) = self.compile(&path, RhaiMap::new())?;
+        self.scripts.push(Script { path, modified, ast, scope, disabled: false });
+    }
+
+    /// the scripts that went over their limits, and won't run again until they change
+    pub fn disabled_scripts(&self) -> impl Iterator<Item=&Path> {
+        self.scripts.iter()
+            .filter(|script| script.disabled)
+            .map(|script| script.path.as_path())
+    }
+
+    /// reload any scripts that have changed since they were last loaded
+    ///
+    /// this happens automatically before each update. if a changed script doesn't compile, the
+    /// old version keeps running (or stays disabled).
+    pub fn reload(&mut self) -> Vec<Error> {
+        let mut errors = vec![];
+        for index in 0..self.scripts.len() {
+            let modified = super::modified(&self.scripts[index].path);
+            if modified == self.scripts[index].modified {
+                continue;
+            }
+            self.scripts[index].modified = modified;
+            let state = self.scripts[index].scope.get_value::<RhaiMap>("state").unwrap_or_default();
+            match self.compile(&self.scripts[index].path, state) {
+                Ok((ast, scope)) => {
+                    log::info!("reloaded script {}", self.scripts[index].path.display());
+                    self.scripts[index].ast = ast;
+                    self.scripts[index].scope = scope;
+                    self.scripts[index].disabled = false;
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        errors
+    }
+
+    /// call the given hook in every script that defines it
+    fn call(&mut self, hook: &'static str, args: Vec<Dynamic>) -> Vec<Error> {
+        let mut errors = vec![];
+        for script in &mut self.scripts {
+            if script.disabled || !script.has_hook(hook, args.len()) {
+                continue;
+            }
+            self.deadline.set(Some(Instant::now() + self.time_budget));
+            let result = self.engine.call_fn_dynamic(&mut script.scope, &script.ast, hook, None, args.clone());
+            self.deadline.set(None);
+            if let Err(error) = result {
+                if over_limits(&error) {
+                    script.disabled = true;
+                }
+                errors.push(script.error(Some(hook), &error));
+            }
+        }
+        errors
+    }
+
+    /// reload any changed scripts, then call their `on_update` hooks
+    pub fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        let mut errors = self.reload();
+        let previous = self.previous.replace(update.clone()).map_or_else(|| Dynamic::from(()), Dynamic::from);
+        errors.extend(self.call("on_update", vec![Dynamic::from(update.clone())]));
+        errors.extend(self.call("on_update", vec![Dynamic::from(update.clone()), previous]));
+        errors
+    }
+
+    /// call the `on_event` hook, and the hook for the given event, if scripts define them
+    pub fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        let mut errors = match rhai::ser::to_dynamic(event) {
+            Ok(event) => self.call("on_event", vec![event]),
+            Err(error) => {
+                log::error!("couldn't pass {:?} to scripts: {}", event, error);
+                vec![]
+            }
+        };
+        errors.extend(self.handle_event_hook(event));
+        errors
+    }
+
+    fn handle_event_hook(&mut self, event: &Event) -> Vec<Error> {
+        fn string(value: &str) -> Dynamic {
+            Dynamic::from(ImmutableString::from(value))
+        }
+
+        let args = match event {
+            Event::GameConnected | Event::GameDisconnected | Event::GameRestarted => vec![],
+            Event::MapChanged { name } => vec![string(name)],
+            Event::MapPhaseChanged { phase } => vec![Dynamic::from(*phase)],
+            Event::RoundStarted { round } => vec![Dynamic::from(*round)],
+            Event::RoundEnded { round, winner } => {
+                vec![Dynamic::from(*round), winner.map_or_else(|| Dynamic::from(()), Dynamic::from)]
+            }
+            Event::BombPlanted | Event::BombDefused | Event::BombExploded => vec![],
+            Event::PlayerDied { steam_id } => vec![string(steam_id)],
+            Event::PlayerGotKill { steam_id, round_kills } => vec![string(steam_id), Dynamic::from(*round_kills)],
+        };
+        self.call(super::event_hook(event), args)
+    }
+}
+
+impl ScriptHooks for ScriptHost {
+    fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        ScriptHost::handle_update(self, update)
+    }
+
+    fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        ScriptHost::handle_event(self, event)
+    }
+}
@@ -0,0 +1,385 @@
+//! the WebAssembly plugin host
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use fehler::throws;
+use serde::Serialize;
+use wasmtime::{Caller, Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap, WasmParams};
+
+use crate::Error;
+use crate::events::Event;
+use crate::update::Update;
+use super::ScriptHooks;
+
+/// the shortest a timer can be set for, so a plugin re-arming one from `on_timer` can't keep the
+/// server busy
+const MIN_TIMER_DELAY: Duration = Duration::from_millis(10);
+
+/// how much a plugin can do before it's stopped and disabled
+#[derive(Clone, Debug)]
+pub struct WasmLimits {
+    /// fuel for each call, roughly one per WebAssembly instruction (default is 10,000,000)
+    pub fuel: u64,
+    /// linear memory the plugin can use, in bytes (default is 16 MiB)
+    pub max_memory: usize,
+    /// timers the plugin can have set at once (default is 64)
+    pub max_timers: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            max_memory: 16 * 1024 * 1024,
+            max_timers: 64,
+        }
+    }
+}
+
+/// an event a plugin emitted with `emit`
+#[derive(Clone, Debug)]
+pub struct PluginEvent {
+    /// the plugin that emitted it
+    pub plugin: PathBuf,
+    /// the name the plugin gave it
+    pub name: String,
+    /// the data the plugin attached to it
+    pub data: serde_json::Value,
+}
+
+type PluginEventListener = Box<dyn FnMut(&PluginEvent)>;
+
+/// what the host API can see of a plugin
+struct PluginState {
+    path: PathBuf,
+    limits: StoreLimits,
+    max_timers: usize,
+    timers: HashMap<i32, Instant>,
+    emitted: Vec<(String, serde_json::Value)>,
+}
+
+/// read a string the plugin passed to the host API
+fn read(caller: &mut Caller<'_, PluginState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    let memory = caller.get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("plugin doesn't export its memory"))?;
+    let bytes = memory.data(&caller)
+        .get(ptr as u32 as usize..)
+        .and_then(|data| data.get(..len as u32 as usize))
+        .ok_or_else(|| wasmtime::Error::msg("plugin passed a string outside its memory"))?;
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+/// the host API, which is all a plugin can import
+fn host_api(linker: &mut Linker<PluginState>) -> wasmtime::Result<()> {
+    linker.func_wrap("csgo_gsi", "log", |mut caller: Caller<'_, PluginState>, level: i32, ptr: i32, len: i32| {
+        let message = read(&mut caller, ptr, len)?;
+        let level = match level {
+            0 => log::Level::Error,
+            1 => log::Level::Warn,
+            2 => log::Level::Info,
+            3 => log::Level::Debug,
+            _ => log::Level::Trace,
+        };
+        log::log!(level, "{}: {}", caller.data().path.display(), message);
+        Ok(())
+    })?;
+    linker.func_wrap("csgo_gsi", "set_timer", |mut caller: Caller<'_, PluginState>, id: i32, millis: i32| {
+        let state = caller.data_mut();
+        if !state.timers.contains_key(&id) && state.timers.len() >= state.max_timers {
+            return Err(wasmtime::Error::msg(format!("plugin set more than {} timers", state.max_timers)));
+        }
+        state.timers.insert(id, Instant::now() + Duration::from_millis(millis.max(0) as u64).max(MIN_TIMER_DELAY));
+        Ok(())
+    })?;
+    linker.func_wrap("csgo_gsi", "cancel_timer", |mut caller: Caller<'_, PluginState>, id: i32| {
+        caller.data_mut().timers.remove(&id);
+    })?;
+    linker.func_wrap("csgo_gsi", "emit", |mut caller: Caller<'_, PluginState>, name_ptr: i32, name_len: i32, data_ptr: i32, data_len: i32| {
+        let name = read(&mut caller, name_ptr, name_len)?;
+        let data = read(&mut caller, data_ptr, data_len)?;
+        let data = serde_json::from_str(&data)?;
+        caller.data_mut().emitted.push((name, data));
+        Ok(())
+    })?;
+    Ok(())
+}
+
+struct Plugin {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    store: Store<PluginState>,
+    instance: Instance,
+    disabled: bool,
+}
+
+impl Plugin {
+    /// call an export that takes plain numbers, if the plugin has it
+    fn call<P: WasmParams>(&mut self, hook: &str, params: P, fuel: u64) -> wasmtime::Result<()> {
+        let function = match self.instance.get_func(&mut self.store, hook) {
+            Some(function) => function.typed::<P, ()>(&self.store)?,
+            None => return Ok(()),
+        };
+        self.store.set_fuel(fuel)?;
+        function.call(&mut self.store, params)
+    }
+
+    /// call an export that takes JSON, if the plugin has it, copying the JSON into its memory
+    fn call_with_json(&mut self, hook: &str, json: &[u8], fuel: u64) -> wasmtime::Result<()> {
+        let function = match self.instance.get_func(&mut self.store, hook) {
+            Some(function) => function.typed::<(i32, i32), ()>(&self.store)?,
+            None => return Ok(()),
+        };
+        let alloc = self.instance.get_typed_func::<i32, i32>(&mut self.store, "alloc")?;
+        let memory = self.instance.get_memory(&mut self.store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("plugin doesn't export its memory"))?;
+        self.store.set_fuel(fuel)?;
+        let ptr = alloc.call(&mut self.store, json.len() as i32)?;
+        memory.write(&mut self.store, ptr as u32 as usize, json)?;
+        function.call(&mut self.store, (ptr, json.len() as i32))
+    }
+
+    fn error(&self, hook: Option<&'static str>, error: &wasmtime::Error) -> Error {
+        let message = match self.disabled {
+            true => format!("{:#} (disabled until it changes)", error),
+            false => format!("{:#}", error),
+        };
+        Error::ScriptError { path: self.path.clone(), hook, message }
+    }
+}
+
+/// loads WebAssembly plugins and calls their exports
+///
+/// plugins can't touch files, the network, or the process: all they can import is the host API
+/// in the `csgo_gsi` module, and a plugin that traps just has its error reported. each call gets
+/// [limited](struct.WasmLimits.html) fuel, and a plugin that runs out is disabled until it
+/// changes. plugins are reloaded whenever they change, which starts them over.
+///
+/// a plugin exports its `memory`, and `alloc(len: i32) -> i32`, which the host calls to get
+/// somewhere to put JSON. then it can export any of
+///
+/// | export | called with |
+/// |--------|-------------|
+/// | `on_update(ptr: i32, len: i32)` | the [update](../update/struct.Update.html) as JSON |
+/// | `on_event(ptr: i32, len: i32)` | any [event](../events/enum.Event.html) as JSON, like `{"type": "round_ended", "round": 3, "winner": "CT"}` |
+/// | `on_timer(id: i32)` | the ID of a timer that's gone off |
+///
+/// and import any of
+///
+/// | import | does |
+/// |--------|------|
+/// | `log(level: i32, ptr: i32, len: i32)` | logs a message, at 0 (error) to 4 (trace) |
+/// | `set_timer(id: i32, millis: i32)` | calls `on_timer(id)` once, after that long (at least 10), replacing any timer with the same ID |
+/// | `cancel_timer(id: i32)` | forgets a timer |
+/// | `emit(name_ptr: i32, name_len: i32, data_ptr: i32, data_len: i32)` | sends a [`PluginEvent`](struct.PluginEvent.html) with JSON data to the [event listeners](#method.add_event_listener) |
+///
+/// timers go off when [`fire_timers`](#method.fire_timers) is called, which a server running the
+/// host does as soon as they're due, as well as before each update and event.
+///
+/// `.wat` files are accepted too.
+pub struct WasmPluginHost {
+    engine: Engine,
+    linker: Linker<PluginState>,
+    plugins: Vec<Plugin>,
+    limits: WasmLimits,
+    listeners: Vec<PluginEventListener>,
+}
+
+impl Default for WasmPluginHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WasmPluginHost {
+    /// create a host with no plugins loaded and the default limits
+    pub fn new() -> Self {
+        Self::with_limits(WasmLimits::default())
+    }
+
+    /// create a host with no plugins loaded and the given limits
+    pub fn with_limits(limits: WasmLimits) -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).expect("fuel is always supported");
+        let mut linker = Linker::new(&engine);
+        host_api(&mut linker).expect("host API names are unique");
+        Self {
+            engine,
+            linker,
+            plugins: vec![],
+            limits,
+            listeners: vec![],
+        }
+    }
+
+    /// add a listener for the events plugins emit
+    pub fn add_event_listener<F: 'static + FnMut(&PluginEvent)>(&mut self, listener: F) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// compile and instantiate a plugin, running its start function if it has one
+    fn instantiate(&self, path: &Path) -> wasmtime::Result<(Store<PluginState>, Instance)> {
+        let module = Module::from_file(&self.engine, path)?;
+        let state = PluginState {
+            path: path.to_path_buf(),
+            limits: StoreLimitsBuilder::new().memory_size(self.limits.max_memory).build(),
+            max_timers: self.limits.max_timers,
+            timers: HashMap::new(),
+            emitted: vec![],
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.limits.fuel)?;
+        let instance = self.linker.instantiate(&mut store, &module)?;
+        Ok((store, instance))
+    }
+
+    /// load the plugin at the given path
+    ///
+    /// it's reloaded whenever it changes. if it doesn't compile, or can't be instantiated, it
+    /// isn't loaded at all.
+    #[throws]
+    pub fn load<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let modified = super::modified(&path);
+        let (store, instance) = self.instantiate(&path)
+            .map_err(|error| Error::ScriptError { path: path.clone(), hook: None, message: format!("{:#}", error) })?;
+        self.plugins.push(Plugin { path, modified, store, instance, disabled: false });
+    }
+
+    /// the plugins that ran out of fuel, and won't run again until they change
+    pub fn disabled_plugins(&self) -> impl Iterator<Item=&Path> {
+        self.plugins.iter()
+            .filter(|plugin| plugin.disabled)
+            .map(|plugin| plugin.path.as_path())
+    }
+
+    /// reload any plugins that have changed since they were last loaded
+    ///
+    /// this happens automatically before each update. if a changed plugin doesn't compile, the
+    /// old version keeps running (or stays disabled).
+    pub fn reload(&mut self) -> Vec<Error> {
+        let mut errors = vec![];
+        for index in 0..self.plugins.len() {
+            let modified = super::modified(&self.plugins[index].path);
+            if modified == self.plugins[index].modified {
+                continue;
+            }
+            let result = self.instantiate(&self.plugins[index].path);
+            let plugin = &mut self.plugins[index];
+            plugin.modified = modified;
+            match result {
+                Ok((store, instance)) => {
+                    log::info!("reloaded plugin {}", plugin.path.display());
+                    plugin.store = store;
+                    plugin.instance = instance;
+                    plugin.disabled = false;
+                }
+                Err(error) => errors.push(Error::ScriptError { path: plugin.path.clone(), hook: None, message: format!("{:#}", error) }),
+            }
+        }
+        errors
+    }
+
+    /// run the given call on every enabled plugin, then pass on whatever they emitted
+    fn call<F>(&mut self, hook: &'static str, mut call: F) -> Vec<Error>
+    where F: FnMut(&mut Plugin, u64) -> wasmtime::Result<()> {
+        let mut errors = vec![];
+        let mut events = vec![];
+        for plugin in &mut self.plugins {
+            if plugin.disabled {
+                continue;
+            }
+            if let Err(error) = call(plugin, self.limits.fuel) {
+                let trap = error.downcast_ref::<Trap>();
+                plugin.disabled = trap == Some(&Trap::OutOfFuel) || trap == Some(&Trap::StackOverflow);
+                errors.push(plugin.error(Some(hook), &error));
+            }
+            let path = &plugin.path;
+            events.extend(plugin.store.data_mut().emitted.drain(..).map(|(name, data)| PluginEvent {
+                plugin: path.clone(),
+                name,
+                data,
+            }));
+        }
+        for event in &events {
+            for listener in &mut self.listeners {
+                listener(event);
+            }
+        }
+        errors
+    }
+
+    /// when the next timer any plugin has set goes off, if there is one
+    pub fn next_timer(&self) -> Option<Instant> {
+        self.plugins.iter()
+            .filter(|plugin| !plugin.disabled)
+            .flat_map(|plugin| plugin.store.data().timers.values().copied())
+            .min()
+    }
+
+    /// call `on_timer` for every timer that's gone off
+    pub fn fire_timers(&mut self) -> Vec<Error> {
+        let now = Instant::now();
+        self.call("on_timer", |plugin, fuel| {
+            let timers = &mut plugin.store.data_mut().timers;
+            let mut due: Vec<(i32, Instant)> = timers.iter()
+                .filter(|(_, &at)| at <= now)
+                .map(|(&id, &at)| (id, at))
+                .collect();
+            due.sort_by_key(|&(_, at)| at);
+            for (id, _) in due {
+                plugin.store.data_mut().timers.remove(&id);
+                plugin.call("on_timer", id, fuel)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// call the given export that takes JSON in every plugin
+    fn call_with_json<T: Serialize>(&mut self, hook: &'static str, value: &T) -> Vec<Error> {
+        match serde_json::to_vec(value) {
+            Ok(json) => self.call(hook, |plugin, fuel| plugin.call_with_json(hook, &json, fuel)),
+            Err(error) => {
+                log::error!("couldn't pass {} to plugins: {}", hook, error);
+                vec![]
+            }
+        }
+    }
+
+    /// reload any changed plugins, fire any timers that have gone off, then call `on_update`
+    pub fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        let mut errors = self.reload();
+        errors.extend(self.fire_timers());
+        errors.extend(self.call_with_json("on_update", update));
+        errors
+    }
+
+    /// fire any timers that have gone off, then call `on_event`
+    pub fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        let mut errors = self.fire_timers();
+        errors.extend(self.call_with_json("on_event", event));
+        errors
+    }
+}
+
+impl ScriptHooks for WasmPluginHost {
+    fn handle_update(&mut self, update: &Update) -> Vec<Error> {
+        WasmPluginHost::handle_update(self, update)
+    }
+
+    fn handle_event(&mut self, event: &Event) -> Vec<Error> {
+        WasmPluginHost::handle_event(self, event)
+    }
+
+    fn next_timer(&self) -> Option<Instant> {
+        WasmPluginHost::next_timer(self)
+    }
+
+    fn fire_timers(&mut self) -> Vec<Error> {
+        WasmPluginHost::fire_timers(self)
+    }
+}
@@ -0,0 +1,187 @@
+//! counters and gauges about the server and the game, in the Prometheus text format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use gotham::helpers::http::response::create_response;
+use gotham::hyper::{Body, Response, StatusCode};
+use gotham::state::{State, FromState};
+
+use crate::Error;
+use crate::update::Update;
+
+/// upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: &[f64] = &[0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// why an update was rejected, as a metric label
+fn reason(error: &Error) -> &'static str {
+    match error {
+        Error::BodyRead { .. } => "body_read",
+        Error::InvalidJson { .. } => "invalid_json",
+        Error::UpdateSchema { .. } => "schema",
+        Error::Unauthorized { .. } => "unauthorized",
+        _ => "other",
+    }
+}
+
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (count, bound) in self.counts.iter_mut().zip(BUCKETS) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (count, bound) in self.counts.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Values {
+    received: u64,
+    rejected: BTreeMap<&'static str, u64>,
+    parse_latency: Histogram,
+    listener_latency: Histogram,
+    queued: u64,
+    last_update: Option<Instant>,
+    round: Option<u64>,
+    scores: Option<(u64, u64)>,
+    health: Option<u64>,
+    armor: Option<u64>,
+    money: Option<u64>,
+}
+
+fn gauge<T: std::fmt::Display>(out: &mut String, name: &str, help: &str, value: Option<T>) {
+    if let Some(value) = value {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+}
+
+/// shared between the update handler and the run loop, which fill it, and the metrics endpoint
+#[derive(Clone, StateData)]
+pub(crate) struct Metrics {
+    inner: Arc<Mutex<Values>>,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let mut values = Values::default();
+        for reason in &["body_read", "invalid_json", "schema", "unauthorized"] {
+            values.rejected.insert(reason, 0);
+        }
+        Self {
+            inner: Arc::new(Mutex::new(values)),
+        }
+    }
+
+    fn values(&self) -> std::sync::MutexGuard<'_, Values> {
+        self.inner.lock().expect("metrics poisoned")
+    }
+
+    pub(crate) fn parsed(&self, latency: Duration) {
+        self.values().parse_latency.observe(latency);
+    }
+
+    pub(crate) fn accepted(&self) {
+        self.values().received += 1;
+    }
+
+    pub(crate) fn rejected(&self, error: &Error) {
+        *self.values().rejected.entry(reason(error)).or_insert(0) += 1;
+    }
+
+    pub(crate) fn queued(&self) {
+        self.values().queued += 1;
+    }
+
+    pub(crate) fn dequeued(&self) {
+        let mut values = self.values();
+        values.queued = values.queued.saturating_sub(1);
+    }
+
+    pub(crate) fn listened(&self, latency: Duration) {
+        self.values().listener_latency.observe(latency);
+    }
+
+    /// keep the game gauges up to date with the latest update
+    pub(crate) fn update(&self, update: &Update) {
+        let mut values = self.values();
+        values.last_update = Some(Instant::now());
+        if let Some(map) = &update.map {
+            values.round = Some(map.round);
+            values.scores = Some((map.team_ct.score, map.team_t.score));
+        }
+        if let Some(state) = update.player.as_ref().and_then(|player| player.state.as_ref()) {
+            values.health = Some(state.health);
+            values.armor = Some(state.armor);
+            values.money = Some(state.money);
+        }
+    }
+
+    fn render(&self) -> String {
+        let values = self.values();
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP csgo_gsi_updates_received_total updates received and accepted");
+        let _ = writeln!(out, "# TYPE csgo_gsi_updates_received_total counter");
+        let _ = writeln!(out, "csgo_gsi_updates_received_total {}", values.received);
+        let _ = writeln!(out, "# HELP csgo_gsi_updates_rejected_total updates rejected, by reason");
+        let _ = writeln!(out, "# TYPE csgo_gsi_updates_rejected_total counter");
+        for (reason, count) in &values.rejected {
+            let _ = writeln!(out, "csgo_gsi_updates_rejected_total{{reason=\"{}\"}} {}", reason, count);
+        }
+        values.parse_latency.write(&mut out, "csgo_gsi_parse_duration_seconds", "time taken to parse each update");
+        values.listener_latency.write(&mut out, "csgo_gsi_listener_duration_seconds", "time taken by recorders and listeners to handle each update");
+        gauge(&mut out, "csgo_gsi_queued_updates", "updates waiting to be handed to listeners", Some(values.queued));
+        gauge(&mut out, "csgo_gsi_seconds_since_last_update", "time since the last update was handled", values.last_update.map(|at| at.elapsed().as_secs_f64()));
+        gauge(&mut out, "csgo_gsi_round", "current round number", values.round);
+        if let Some((ct, t)) = values.scores {
+            let _ = writeln!(out, "# HELP csgo_gsi_team_score rounds won, by team");
+            let _ = writeln!(out, "# TYPE csgo_gsi_team_score gauge");
+            let _ = writeln!(out, "csgo_gsi_team_score{{team=\"CT\"}} {}", ct);
+            let _ = writeln!(out, "csgo_gsi_team_score{{team=\"T\"}} {}", t);
+        }
+        gauge(&mut out, "csgo_gsi_player_health", "player health", values.health);
+        gauge(&mut out, "csgo_gsi_player_armor", "player armor", values.armor);
+        gauge(&mut out, "csgo_gsi_player_money", "player money", values.money);
+        out
+    }
+}
+
+pub(crate) fn get_metrics(state: State) -> (State, Response<Body>) {
+    let body = Metrics::borrow_from(&state).render();
+    let content_type = "text/plain; version=0.0.4".parse().expect("valid content type");
+    let response = create_response(&state, StatusCode::OK, content_type, body);
+    (state, response)
+}
@@ -0,0 +1,824 @@
+use std::collections::HashMap;
+use std::future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use fehler::{throws, throw};
+use futures::future::abortable;
+use gotham::handler::HandlerError;
+use gotham::helpers::http::response::create_empty_response;
+use gotham::hyper::{body, Body, Response, StatusCode};
+use gotham::middleware::state::StateMiddleware;
+use gotham::pipeline::new_pipeline;
+use gotham::pipeline::single::single_pipeline;
+use gotham::router::builder::{DefineSingleRoute, build_router};
+use gotham::router::builder::DrawRoutes;
+use gotham::router::Router;
+use gotham::state::{State, FromState};
+
+use tokio::net::TcpListener;
+
+use crate::{GSIConfig, Error, Subscription, install_dir, update};
+use crate::capture::{CapturedUpdate, Recorder};
+use crate::events::{Event, EventDetector};
+#[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+use crate::scripting::ScriptHooks;
+use crate::team::ClientId;
+use crate::update::typed::{SubscriptionSet, TypedUpdate};
+
+mod state;
+use state::StateStore;
+
+mod metrics;
+use metrics::Metrics;
+
+#[cfg(feature = "overlay")]
+mod overlay;
+
+mod required;
+use required::RequiredSections;
+
+mod service;
+pub use service::Service;
+use service::ServiceHandlers;
+
+mod sse;
+use sse::EventStream;
+
+mod watchdog;
+use watchdog::Watchdog;
+
+mod websocket;
+use websocket::WebSocketHub;
+
+/// the sections of an update that clients can ask for separately
+const SECTIONS: &[&str] = &["map", "player", "provider", "round"];
+
+/// the sections an update has, as JSON, leaving out its auth
+fn sections(update: &update::Update) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(update) {
+        Ok(serde_json::Value::Object(update)) => update.into_iter()
+            .filter(|(section, value)| SECTIONS.contains(&section.as_str()) && !value.is_null())
+            .collect(),
+        Ok(_) => serde_json::Map::new(),
+        Err(err) => {
+            log::error!("failed to serialize update: {}", err);
+            serde_json::Map::new()
+        }
+    }
+}
+
+type Listener = Box<dyn FnMut(&update::Update)>;
+type CaptureListener = Box<dyn FnMut(&CapturedUpdate)>;
+type ClientListener = Box<dyn FnMut(&ClientId, &update::Update)>;
+type EventListener = Box<dyn FnMut(&Event)>;
+type TypedListener<S> = Box<dyn FnMut(&TypedUpdate<S>)>;
+type ErrorHook = Box<dyn FnMut(&Error)>;
+
+/// a server that listens for GSI updates
+///
+/// `S` is the set of subscriptions its config was [typed](update/typed/index.html) with, if any.
+pub struct GSIServer<S: SubscriptionSet = ()> {
+    port: u16,
+    bind_addr: IpAddr,
+    advertised_uri: Option<String>,
+    listener: Option<TcpListener>,
+    tx: mpsc::SyncSender<Option<Received>>,
+    rx: Option<mpsc::Receiver<Option<Received>>>,
+    serve_state: bool,
+    history_len: usize,
+    allow_origin: Option<String>,
+    serve_metrics: bool,
+    metrics: Metrics,
+    #[cfg(feature = "overlay")]
+    serve_overlay: bool,
+    #[cfg(feature = "overlay")]
+    overlay_theme: Option<PathBuf>,
+    serve_sse: bool,
+    event_stream: EventStream,
+    serve_websocket: bool,
+    websocket: WebSocketHub,
+    config: GSIConfig<S>,
+    installed: bool,
+    services: Vec<Service>,
+    required: RequiredSections,
+    listeners: Vec<Listener>,
+    typed_listeners: Vec<TypedListener<S>>,
+    capture_listeners: Vec<CaptureListener>,
+    client_listeners: Vec<ClientListener>,
+    event_listeners: Vec<EventListener>,
+    event_detector: EventDetector,
+    #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+    script_hosts: Vec<Box<dyn ScriptHooks>>,
+    heartbeat_grace: Duration,
+    recorders: Vec<Recorder>,
+    error_hooks: Vec<ErrorHook>,
+}
+
+impl<S: SubscriptionSet> GSIServer<S> {
+    /// create a new server with the given configuration and port
+    ///
+    /// if the port is 0, the OS will pick a free port when the server is [bound](#method.bind).
+    pub fn new(config: GSIConfig<S>, port: u16) -> Self {
+        let (tx, rx) = mpsc::sync_channel(128);
+        Self {
+            port,
+            bind_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            advertised_uri: None,
+            listener: None,
+            tx,
+            rx: Some(rx),
+            serve_state: false,
+            history_len: 0,
+            allow_origin: None,
+            serve_metrics: false,
+            metrics: Metrics::new(),
+            #[cfg(feature = "overlay")]
+            serve_overlay: false,
+            #[cfg(feature = "overlay")]
+            overlay_theme: None,
+            serve_sse: false,
+            event_stream: EventStream::new(0),
+            serve_websocket: false,
+            websocket: WebSocketHub::new(),
+            config,
+            installed: false,
+            services: vec![],
+            required: RequiredSections::new(),
+            listeners: vec![],
+            typed_listeners: vec![],
+            capture_listeners: vec![],
+            client_listeners: vec![],
+            event_listeners: vec![],
+            event_detector: EventDetector::new(),
+            #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+            script_hosts: vec![],
+            heartbeat_grace: Duration::from_secs(5),
+            recorders: vec![],
+            error_hooks: vec![],
+        }
+    }
+
+    /// listen on the given address instead of `127.0.0.1` (IPv4 or IPv6)
+    ///
+    /// if this isn't a loopback address, the configuration must include auth, which will be
+    /// checked on every update. call this before installing, so the installed URI is right.
+    pub fn bind_to<A: Into<IpAddr>>(&mut self, addr: A) {
+        self.bind_addr = addr.into();
+    }
+
+    /// tell CS:GO to send updates to the given URI (e.g. `http://192.168.1.2:31337`), for when the
+    /// game is running on a different machine than this server
+    ///
+    /// call this before installing, so the installed URI is right.
+    pub fn advertise_uri<U: Into<String>>(&mut self, uri: U) {
+        self.advertised_uri = Some(uri.into());
+    }
+
+    /// the configuration that will be installed, including what listeners have required
+    pub fn config(&self) -> &GSIConfig<S> {
+        &self.config
+    }
+
+    /// the URI CS:GO will be told to send updates to
+    pub fn uri(&self) -> String {
+        if let Some(uri) = &self.advertised_uri {
+            return uri.clone();
+        }
+        let addr = match self.bind_addr {
+            IpAddr::V4(addr) if addr.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(addr) if addr.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            addr => addr,
+        };
+        format!("http://{}", SocketAddr::new(addr, self.port))
+    }
+
+    /// start listening, returning the address actually bound to
+    ///
+    /// this happens automatically when running the server, but binding first lets you find out
+    /// which port was picked when the server was created with port 0. the server must then be run
+    /// on the same tokio runtime.
+    #[throws]
+    pub async fn bind(&mut self) -> SocketAddr {
+        if let Some(addr) = self.local_addr() {
+            return addr;
+        }
+        let listener = TcpListener::bind(SocketAddr::new(self.bind_addr, self.port)).await
+            .map_err(|err| Error::ServerError { description: "failed to bind", cause: Some(Box::new(err)) })?;
+        let addr = listener.local_addr()
+            .map_err(|err| Error::ServerError { description: "failed to get bound address", cause: Some(Box::new(err)) })?;
+        self.port = addr.port();
+        self.listener = Some(listener);
+        addr
+    }
+
+    /// the address this server is listening on, if it has been bound
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|listener| listener.local_addr().ok())
+    }
+
+    /// also serve the latest update as JSON, for clients that can't register listeners
+    ///
+    /// - `GET /state` returns the latest update
+    /// - `GET /state/map`, `/state/player`, `/state/provider` and `/state/round` return one section of it
+    /// - `GET /history?since=<id>` returns up to `history_len` of the most recent updates, as
+    ///   `{"id": ..., "received_at": ..., "update": ...}` objects, with IDs after `since`
+    ///
+    /// the `/state` endpoints return 404 if there's nothing to return yet, and `/history` returns
+    /// `[]`.
+    pub fn serve_state(&mut self, history_len: usize) {
+        self.serve_state = true;
+        self.history_len = history_len;
+    }
+
+    /// also stream updates to browsers as [Server-Sent Events][sse], at `GET /events`
+    ///
+    /// each section of each update is sent as its own event, with the section name (`map`,
+    /// `player`, `provider` or `round`) as the event type and the section as JSON as the data.
+    /// the last `buffer_len` events are kept, so a client that reconnects with `Last-Event-ID`
+    /// (as `EventSource` does automatically) gets the ones it missed.
+    ///
+    /// [sse]: https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events
+    pub fn serve_sse(&mut self, buffer_len: usize) {
+        self.serve_sse = true;
+        self.event_stream = EventStream::new(buffer_len);
+    }
+
+    /// also serve [Prometheus](https://prometheus.io/) metrics at `GET /metrics`
+    ///
+    /// these include updates received and rejected (by reason), how long updates take to parse
+    /// and to handle, how many are waiting to be handled, the time since the last one, and the
+    /// round, scores, and the player's health, armor and money.
+    pub fn serve_metrics(&mut self) {
+        self.serve_metrics = true;
+    }
+
+    /// also serve a ready-made scoreboard at `GET /overlay`, for use as a browser source in
+    /// streaming software
+    ///
+    /// the page shows team names and scores, the round phase, the bomb state, and the player's
+    /// health, armor and money. it starts from [`/state`](#method.serve_state) and updates live
+    /// over [`/events`](#method.serve_sse), so this turns both of those on if they aren't already.
+    #[cfg(feature = "overlay")]
+    pub fn serve_overlay(&mut self) {
+        self.serve_overlay = true;
+        self.serve_state = true;
+        if !self.serve_sse {
+            self.serve_sse(0);
+        }
+    }
+
+    /// style the overlay with the CSS file at the given path instead of the built-in theme
+    ///
+    /// the file is read every time the page loads, so changes show up when it's refreshed.
+    #[cfg(feature = "overlay")]
+    pub fn overlay_theme<P: Into<PathBuf>>(&mut self, path: P) {
+        self.overlay_theme = Some(path.into());
+    }
+
+    /// also push updates to WebSocket clients as they arrive, at `GET /ws`
+    ///
+    /// each update is sent as `{"type": "update", "update": {...}}`, and if `events` is true, each
+    /// [event](events/enum.Event.html) derived from it is sent as `{"type": "event", "event": {...}}`.
+    /// clients can connect to `/ws?topics=map,round` to only get those sections of each update
+    /// and the events that came from them (topics are `map`, `player`, `provider` and `round`).
+    pub fn serve_websocket(&mut self, events: bool) {
+        self.serve_websocket = true;
+        self.websocket.send_events(events);
+    }
+
+    /// allow browser pages on the given origin (or `*` for any origin) to read the JSON endpoints
+    pub fn allow_origin<O: Into<String>>(&mut self, origin: O) {
+        self.allow_origin = Some(origin.into());
+    }
+
+    /// host another service, with its own config, auth and listeners, at `/svc/<name>` on the
+    /// same port
+    ///
+    /// its config is installed and uninstalled along with this server's. its name must be
+    /// different from every other service's, including this server's own.
+    #[throws]
+    pub fn add_service(&mut self, config: GSIConfig) -> &mut Service {
+        let name = config.service_name();
+        if name == self.config.service_name() || self.services.iter().any(|service| service.config().service_name() == name) {
+            throw!(Error::ServerError { description: "service names must be unique", cause: None });
+        }
+        self.services.push(Service::new(config));
+        self.services.last_mut().expect("service was just added")
+    }
+
+    /// install this server's configuration, and its services', into the given `/path/to/csgo/cfg/` folder
+    ///
+    /// if the server was created with port 0, it must be [bound](#method.bind) first.
+    #[throws]
+    pub fn install_into<P: Into<PathBuf>>(&mut self, cfg_folder: P) {
+        if self.port == 0 && self.advertised_uri.is_none() {
+            throw!(Error::ConfigInstallError { description: "server must be bound before installing when using port 0", cause: None });
+        }
+        let cfg_folder = cfg_folder.into();
+        let uri = self.uri();
+        self.config.install_into(&cfg_folder, uri.clone())?;
+        for service in &self.services {
+            service.config().install_into(&cfg_folder, service.config().service_uri(&uri))?;
+        }
+        self.installed = true;
+    }
+
+    /// install this server's configuration into the autodiscovered `/path/to/csgo/cfg/` folder, if it can be found
+    #[throws]
+    pub fn install(&mut self) {
+        self.install_into(install_dir::discover_cfg_folder()?)?;
+    }
+
+    /// remove this server's configuration, and its services', from the given `/path/to/csgo/cfg/` folder
+    #[throws]
+    pub fn uninstall_from<P: Into<PathBuf>>(&mut self, cfg_folder: P) {
+        let cfg_folder = cfg_folder.into();
+        self.config.uninstall_from(&cfg_folder)?;
+        for service in &self.services {
+            service.config().uninstall_from(&cfg_folder)?;
+        }
+        self.installed = false;
+    }
+
+    /// remove this server's configuration from the autodiscovered `/path/to/csgo/cfg/` folder, if it can be found
+    #[throws]
+    pub fn uninstall(&mut self) {
+        self.uninstall_from(install_dir::discover_cfg_folder()?)?;
+    }
+
+    /// add an update listener
+    pub fn add_listener<F: 'static + FnMut(&update::Update)>(&mut self, listener: F) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// add an update listener that needs the given subscriptions
+    ///
+    /// they're added to the config if it doesn't already have them, so call this before
+    /// installing. if a section they add still hasn't shown up after a while, a warning is logged.
+    /// spectator-only sections can't be checked, so requiring one just logs that.
+    pub fn add_listener_requiring<I, T, F>(&mut self, subscriptions: I, listener: F)
+    where I: IntoIterator<Item=T>, T: Into<Subscription>, F: 'static + FnMut(&update::Update) {
+        self.require(subscriptions.into_iter().map(Into::into).collect());
+        self.add_listener(listener);
+    }
+
+    /// add a listener that gets updates with exactly the sections subscribed to in `S`
+    ///
+    /// updates that are missing a subscribed section are reported as errors instead.
+    pub fn add_typed_listener<F: 'static + FnMut(&TypedUpdate<S>)>(&mut self, listener: F) {
+        self.require(S::subscriptions());
+        self.typed_listeners.push(Box::new(listener));
+    }
+
+    fn require(&mut self, subscriptions: Vec<Subscription>) {
+        let added = self.config.require(subscriptions.iter().copied());
+        if self.installed && !added.is_empty() {
+            log::warn!("listener needs {:?}, which the installed config doesn't subscribe to; install it again", added);
+        }
+        self.required.require(subscriptions);
+    }
+
+    /// add a listener that also gets the raw request body and the time the update was received
+    pub fn add_capture_listener<F: 'static + FnMut(&CapturedUpdate)>(&mut self, listener: F) {
+        self.capture_listeners.push(Box::new(listener));
+    }
+
+    /// add a listener that's also told which [client](team/struct.ClientId.html) each update came from,
+    /// for when several players' games send updates to the same server
+    ///
+    /// this needs the `Provider` subscription, which is added to the config if it's missing, so
+    /// call this before installing. updates without provider info are skipped.
+    pub fn add_client_listener<F: 'static + FnMut(&ClientId, &update::Update)>(&mut self, listener: F) {
+        self.require(vec![Subscription::Provider]);
+        self.client_listeners.push(Box::new(listener));
+    }
+
+    /// add a listener for the [events](events/enum.Event.html) derived from each update
+    pub fn add_event_listener<F: 'static + FnMut(&Event)>(&mut self, listener: F) {
+        self.event_listeners.push(Box::new(listener));
+    }
+
+    /// run the given scripts' hooks for every update and event
+    ///
+    /// script errors are reported to the [error hooks](#method.on_error).
+    #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+    pub fn add_script_host<H: 'static + ScriptHooks>(&mut self, host: H) {
+        self.script_hosts.push(Box::new(host));
+    }
+
+    /// how long past the configured heartbeat to wait for an update before the game counts as
+    /// disconnected (default is 5 seconds)
+    ///
+    /// event listeners are told when the game connects, disconnects, or restarts.
+    pub fn heartbeat_grace(&mut self, grace: Duration) {
+        self.heartbeat_grace = grace;
+    }
+
+    /// record every update with the given recorder
+    pub fn add_recorder(&mut self, recorder: Recorder) {
+        self.recorders.push(recorder);
+    }
+
+    /// add a hook that's called with every error encountered while running, such as updates
+    /// that couldn't be parsed or failed to record
+    ///
+    /// errors are also logged with the [`log`](https://docs.rs/log) crate.
+    pub fn on_error<F: 'static + FnMut(&Error)>(&mut self, hook: F) {
+        self.error_hooks.push(Box::new(hook));
+    }
+
+    fn router(&self) -> Router {
+        let tx = &self.tx;
+        let store = StateStore::new(self.history_len, self.allow_origin.clone());
+        let mut update_handler = UpdateHandler::new(tx, None, self.config.auth().clone(), Some(store.clone()), self.metrics.clone());
+        update_handler.keep_json = !self.typed_listeners.is_empty();
+        let service_handlers = ServiceHandlers {
+            handlers: self.services.iter().enumerate()
+                .map(|(index, service)| {
+                    let handler = UpdateHandler::new(tx, Some(index), service.config().auth().clone(), None, self.metrics.clone());
+                    (service.config().service_name().to_string(), handler)
+                })
+                .collect(),
+        };
+
+        let pipeline = new_pipeline()
+            .add(StateMiddleware::new(update_handler))
+            .add(StateMiddleware::new(service_handlers))
+            .add(StateMiddleware::new(store))
+            .add(StateMiddleware::new(self.metrics.clone()))
+            .add(StateMiddleware::new(self.event_stream.clone()))
+            .add(StateMiddleware::new(self.websocket.clone()))
+            .build();
+        let (chain, pipelines) = single_pipeline(pipeline);
+
+        build_router(chain, pipelines, |route| {
+            route
+                .post("/")
+                .to_async(handle_update);
+            if !self.services.is_empty() {
+                route
+                    .post("/svc/:name")
+                    .with_path_extractor::<service::ServicePath>()
+                    .to_async(service::handle_service_update);
+            }
+            if self.serve_state {
+                route
+                    .get("/state")
+                    .to(state::get_state);
+                route
+                    .get("/state/:section")
+                    .with_path_extractor::<state::SectionPath>()
+                    .to(state::get_state_section);
+                route
+                    .get("/history")
+                    .with_query_string_extractor::<state::HistoryQuery>()
+                    .to(state::get_history);
+            }
+            if self.serve_metrics {
+                route
+                    .get("/metrics")
+                    .to(metrics::get_metrics);
+            }
+            #[cfg(feature = "overlay")]
+            {
+                if self.serve_overlay {
+                    route
+                        .get("/overlay")
+                        .to(overlay::get_index);
+                    match &self.overlay_theme {
+                        Some(theme) => route
+                            .get("/overlay/theme.css")
+                            .to_file(theme.clone()),
+                        None => route
+                            .get("/overlay/theme.css")
+                            .to(overlay::get_default_theme),
+                    }
+                }
+            }
+            if self.serve_sse {
+                route
+                    .get("/events")
+                    .to(sse::get_events);
+            }
+            if self.serve_websocket {
+                route
+                    .get("/ws")
+                    .with_query_string_extractor::<websocket::TopicQuery>()
+                    .to(websocket::handle_websocket);
+            }
+        })
+    }
+
+    fn report(&mut self, error: &Error) {
+        for hook in &mut self.error_hooks {
+            hook(error)
+        }
+    }
+
+    /// how long until the next timer a script host has set goes off, if there is one
+    fn next_timer(&self) -> Option<Duration> {
+        #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+        let next_timer = self.script_hosts.iter().filter_map(|host| host.next_timer()).min();
+        #[cfg(not(any(feature = "lua", feature = "rhai", feature = "wasm")))]
+        let next_timer: Option<Instant> = None;
+        next_timer.map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    fn fire_timers(&mut self) {
+        #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+        {
+            let errors = self.script_hosts.iter_mut()
+                .flat_map(|host| host.fire_timers())
+                .collect::<Vec<_>>();
+            for err in errors {
+                log::warn!("{}", err);
+                self.report(&err);
+            }
+        }
+    }
+
+    fn dispatch(&mut self, event: &Event) {
+        for callback in &mut self.event_listeners {
+            callback(event)
+        }
+        if self.serve_websocket {
+            self.websocket.send_event(event);
+        }
+        #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+        {
+            let errors = self.script_hosts.iter_mut()
+                .flat_map(|host| host.handle_event(event))
+                .collect::<Vec<_>>();
+            for err in errors {
+                log::warn!("{}", err);
+                self.report(&err);
+            }
+        }
+    }
+
+    /// something that can stop this server once it's running
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle { tx: self.tx.clone() }
+    }
+
+    /// run the server (will block until it's [shut down](#method.shutdown_handle))
+    #[throws]
+    pub async fn run(mut self) {
+        let unauthenticated = self.config.auth().is_empty() || self.services.iter().any(|service| service.config().auth().is_empty());
+        if !self.bind_addr.is_loopback() && unauthenticated {
+            throw!(Error::ServerError { description: "binding to a non-loopback address requires auth", cause: None });
+        }
+        self.bind().await?;
+        if !self.installed {
+            self.install()?;
+        }
+
+        let rx = self.rx.take().expect("server only runs once");
+        let listener = self.listener.take().expect("server was just bound");
+        let router = self.router();
+        let (serve, serving) = abortable(gotham::bind_server(listener, router, |socket| future::ready(Ok(socket))));
+        tokio::spawn(serve);
+
+        let mut watchdog = Watchdog::new(self.config.heartbeat(), self.heartbeat_grace);
+        loop {
+            let timeout = match (watchdog.remaining(), self.next_timer()) {
+                (Some(remaining), Some(next_timer)) => Some(remaining.min(next_timer)),
+                (remaining, next_timer) => remaining.or(next_timer),
+            };
+            let received = match timeout {
+                Some(timeout) => match rx.recv_timeout(timeout) {
+                    Ok(received) => received,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        if let Some(event) = watchdog.check() {
+                            self.event_detector.reset();
+                            self.dispatch(&event);
+                        }
+                        self.fire_timers();
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                },
+                None => match rx.recv() {
+                    Ok(received) => received,
+                    Err(_) => break,
+                },
+            };
+            // `None` means the server's been shut down
+            let (service, received, json) = match received {
+                Some(received) => received,
+                None => break,
+            };
+            self.metrics.dequeued();
+            let captured = match received {
+                Ok(captured) => captured,
+                Err(err) => {
+                    self.report(&err);
+                    continue;
+                }
+            };
+            if let Some(service) = service {
+                let started = Instant::now();
+                self.services[service].handle(&captured.update);
+                self.metrics.listened(started.elapsed());
+                continue;
+            }
+            self.metrics.update(&captured.update);
+            for subscription in self.required.update(&captured.update) {
+                log::warn!("a listener needs {:?}, but it hasn't been in any update yet", subscription);
+            }
+            let started = Instant::now();
+            // only parsed if there are typed listeners, and then only once, from the same JSON
+            let typed = json.map(|json| TypedUpdate::<S>::from_json_value(&json, &captured.raw));
+            let mut errors = vec![];
+            for recorder in &mut self.recorders {
+                if let Err(err) = recorder.record(&captured) {
+                    log::error!("{}", err);
+                    errors.push(err);
+                }
+            }
+            for err in errors {
+                self.report(&err);
+            }
+            for callback in &mut self.capture_listeners {
+                callback(&captured)
+            }
+            for callback in &mut self.listeners {
+                callback(&captured.update)
+            }
+            #[cfg(any(feature = "lua", feature = "rhai", feature = "wasm"))]
+            {
+                let errors = self.script_hosts.iter_mut()
+                    .flat_map(|host| host.handle_update(&captured.update))
+                    .collect::<Vec<_>>();
+                for err in errors {
+                    log::warn!("{}", err);
+                    self.report(&err);
+                }
+            }
+            if !self.client_listeners.is_empty() {
+                if let Some(client) = ClientId::of(&captured.update) {
+                    for callback in &mut self.client_listeners {
+                        callback(&client, &captured.update)
+                    }
+                }
+            }
+            match typed {
+                Some(Ok(typed)) => {
+                    for callback in &mut self.typed_listeners {
+                        callback(&typed)
+                    }
+                }
+                Some(Err(err)) => {
+                    log::warn!("{}", err);
+                    self.report(&err);
+                }
+                None => {}
+            }
+            if self.serve_sse {
+                self.event_stream.send_update(&captured.update);
+            }
+            if self.serve_websocket {
+                self.websocket.send_update(&captured.update);
+            }
+
+            let mut events = watchdog.update(&captured.update);
+            if events.contains(&Event::GameRestarted) {
+                self.event_detector.reset();
+            }
+            events.extend(self.event_detector.detect(&captured.update));
+            for event in &events {
+                self.dispatch(event);
+            }
+            self.metrics.listened(started.elapsed());
+        }
+        serving.abort();
+        for recorder in std::mem::take(&mut self.recorders) {
+            if let Err(err) = recorder.close() {
+                log::error!("{}", err);
+                self.report(&err);
+            }
+        }
+    }
+}
+
+/// which service an update's for, if any, the update, and the JSON it was parsed from, if that's
+/// needed again
+type Received = (Option<usize>, Result<CapturedUpdate, Error>, Option<serde_json::Value>);
+
+/// stops a running [`GSIServer`](struct.GSIServer.html), from any thread
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    tx: mpsc::SyncSender<Option<Received>>,
+}
+
+impl ShutdownHandle {
+    /// make the server stop listening and return from [`run`](struct.GSIServer.html#method.run)
+    ///
+    /// updates that have already been received are handled first.
+    pub fn shutdown(&self) {
+        // if the server's gone, it's already stopped
+        let _ = self.tx.send(None);
+    }
+}
+
+#[derive(Clone, StateData)]
+struct UpdateHandler {
+    inner: mpsc::SyncSender<Option<Received>>,
+    service: Option<usize>,
+    auth: HashMap<String, String>,
+    store: Option<StateStore>,
+    metrics: Metrics,
+    /// whether to send on the JSON updates were parsed from, for typed listeners
+    keep_json: bool,
+}
+
+impl UpdateHandler {
+    fn new(tx: &mpsc::SyncSender<Option<Received>>, service: Option<usize>, auth: HashMap<String, String>, store: Option<StateStore>, metrics: Metrics) -> Self {
+        Self {
+            inner: tx.clone(),
+            service,
+            auth,
+            store,
+            metrics,
+            keep_json: false,
+        }
+    }
+
+    fn is_authorized(&self, update: &update::Update) -> bool {
+        self.auth.iter().all(|(key, value)| update.auth.get(key) == Some(value))
+    }
+
+    #[throws]
+    fn send(&self, received: Result<CapturedUpdate, Error>, json: Option<serde_json::Value>) {
+        self.metrics.queued();
+        self.inner.send(Some((self.service, received, json))).map_err(|_| {
+            self.metrics.dequeued();
+            Error::ListenerGone
+        })?;
+    }
+}
+
+fn reject(state: State, error: Error, status: StatusCode) -> (State, Response<Body>) {
+    log::warn!("{}", error);
+    UpdateHandler::borrow_from(&state).metrics.rejected(&error);
+    let status = match UpdateHandler::borrow_from(&state).send(Err(error), None) {
+        Ok(()) => status,
+        Err(err) => {
+            log::error!("{}", err);
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+    let response = create_empty_response(&state, status);
+    (state, response)
+}
+
+#[throws((State, HandlerError))]
+pub async fn handle_update(mut state: State) -> (State, Response<Body>) {
+    let body = state.try_take::<Body>();
+    let body = match body {
+        Some(body) => body,
+        None => {
+            let response = create_empty_response(&state, StatusCode::BAD_REQUEST);
+            return (state, response);
+        }
+    };
+    let body = body::to_bytes(body).await;
+    let body = match body {
+        Ok(body) => body,
+        Err(err) => {
+            let error = Error::BodyRead { cause: Box::new(err) };
+            return reject(state, error, StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let raw = String::from_utf8_lossy(body.as_ref()).into_owned();
+    let parse_started = Instant::now();
+    let data = update::parse_json(&raw)
+        .and_then(|json| update::parse_value::<update::Update>(&json, &raw).map(|data| (data, json)));
+    UpdateHandler::borrow_from(&state).metrics.parsed(parse_started.elapsed());
+    let (data, json) = match data {
+        Ok(data) => data,
+        Err(error) => return reject(state, error, StatusCode::BAD_REQUEST),
+    };
+    if !UpdateHandler::borrow_from(&state).is_authorized(&data) {
+        return reject(state, Error::Unauthorized { raw }, StatusCode::UNAUTHORIZED);
+    }
+    let update_handler = UpdateHandler::borrow_from(&state);
+    let captured = CapturedUpdate::now(raw, data);
+    update_handler.metrics.accepted();
+    if let Some(store) = &update_handler.store {
+        store.push(&captured);
+    }
+    let sent = update_handler.send(Ok(captured), Some(json).filter(|_| update_handler.keep_json));
+    let status = match sent {
+        Ok(()) => StatusCode::OK,
+        Err(err) => {
+            log::error!("{}", err);
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+    let response = create_empty_response(&state, status);
+    (state, response)
+}
@@ -0,0 +1,18 @@
+//! a ready-made scoreboard page, for use as a browser source in streaming software
+
+use gotham::helpers::http::response::create_response;
+use gotham::hyper::{Body, Response, StatusCode};
+use gotham::state::State;
+
+const INDEX: &str = include_str!("overlay/index.html");
+const DEFAULT_THEME: &str = include_str!("overlay/theme.css");
+
+pub(crate) fn get_index(state: State) -> (State, Response<Body>) {
+    let response = create_response(&state, StatusCode::OK, mime::TEXT_HTML_UTF_8, INDEX);
+    (state, response)
+}
+
+pub(crate) fn get_default_theme(state: State) -> (State, Response<Body>) {
+    let response = create_response(&state, StatusCode::OK, mime::TEXT_CSS_UTF_8, DEFAULT_THEME);
+    (state, response)
+}
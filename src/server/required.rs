@@ -0,0 +1,84 @@
+//! notices when a section that a listener needs never shows up in updates
+
+use std::collections::HashSet;
+
+use crate::Subscription;
+use crate::update::Update;
+
+/// how many updates to wait for a required section before warning about it
+const WARN_AFTER: u64 = 20;
+
+/// whether an update includes what the given subscription adds, or `None` if that can't be told
+fn includes(update: &Update, subscription: Subscription) -> Option<bool> {
+    let player = update.player.as_ref();
+    Some(match subscription {
+        Subscription::MapRoundWins => update.map.as_ref().is_some_and(|map| !map.round_wins.is_empty()),
+        Subscription::Map => update.map.is_some(),
+        Subscription::PlayerID => player.is_some(),
+        Subscription::PlayerMatchStats => player.is_some_and(|player| player.match_stats.is_some()),
+        Subscription::PlayerState => player.is_some_and(|player| player.state.is_some()),
+        Subscription::PlayerWeapons => player.is_some_and(|player| !player.weapons.is_empty()),
+        Subscription::Provider => update.provider.is_some(),
+        Subscription::Round => update.round.is_some(),
+        _ => return None,
+    })
+}
+
+pub(crate) struct RequiredSections {
+    missing: HashSet<Subscription>,
+    updates: u64,
+}
+
+impl RequiredSections {
+    pub(crate) fn new() -> Self {
+        Self {
+            missing: HashSet::new(),
+            updates: 0,
+        }
+    }
+
+    pub(crate) fn require<I: IntoIterator<Item=Subscription>>(&mut self, subscriptions: I) {
+        for subscription in subscriptions {
+            if Subscription::SPECTATOR_ONLY.contains(&subscription) {
+                log::warn!("a listener needs {:?}, but spectator sections aren't parsed, so it can't be checked", subscription);
+            } else {
+                self.missing.insert(subscription);
+            }
+        }
+    }
+
+    /// note which required sections this update included, returning the ones that still haven't
+    /// arrived once it's been long enough to give up on them
+    pub(crate) fn update(&mut self, update: &Update) -> Vec<Subscription> {
+        if self.missing.is_empty() {
+            return vec![];
+        }
+        self.missing.retain(|subscription| includes(update, *subscription) == Some(false));
+        self.updates += 1;
+        if self.updates < WARN_AFTER {
+            return vec![];
+        }
+        // only warn once
+        self.missing.drain().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_sections_are_reported_once() {
+        let mut update: serde_json::Value = serde_json::from_str(include_str!("../../tests/fixtures/update.json")).unwrap();
+        update["player"].as_object_mut().unwrap().remove("match_stats");
+        let update: Update = serde_json::from_value(update).unwrap();
+
+        let mut required = RequiredSections::new();
+        required.require(vec![Subscription::Map, Subscription::PlayerMatchStats, Subscription::Bomb]);
+        for _ in 1..WARN_AFTER {
+            assert!(required.update(&update).is_empty());
+        }
+        assert_eq!(required.update(&update), vec![Subscription::PlayerMatchStats]);
+        assert!(required.update(&update).is_empty());
+    }
+}
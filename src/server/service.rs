@@ -0,0 +1,94 @@
+//! extra services hosted by one server, each with its own config and listeners
+
+use std::collections::HashMap;
+
+use fehler::throws;
+use gotham::handler::HandlerError;
+use gotham::helpers::http::response::create_empty_response;
+use gotham::hyper::{Body, Response, StatusCode};
+use gotham::state::{State, FromState};
+use serde::Deserialize;
+
+use crate::{GSIConfig, Subscription, update};
+use super::{Listener, UpdateHandler};
+use super::required::RequiredSections;
+
+/// a service hosted by a [`GSIServer`](struct.GSIServer.html) alongside its main one
+///
+/// it's installed as its own `gamestate_integration_<name>.cfg`, gets updates at `/svc/<name>`,
+/// and checks its own auth.
+pub struct Service {
+    config: GSIConfig,
+    listeners: Vec<Listener>,
+    required: RequiredSections,
+}
+
+impl Service {
+    pub(crate) fn new(config: GSIConfig) -> Self {
+        Self {
+            config,
+            listeners: vec![],
+            required: RequiredSections::new(),
+        }
+    }
+
+    /// the configuration that will be installed, including what listeners have required
+    pub fn config(&self) -> &GSIConfig {
+        &self.config
+    }
+
+    /// add an update listener
+    pub fn add_listener<F: 'static + FnMut(&update::Update)>(&mut self, listener: F) -> &mut Self {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// add an update listener that needs the given subscriptions, which are added to the config
+    /// if it doesn't already have them
+    ///
+    /// call this before installing.
+    pub fn add_listener_requiring<I, T, F>(&mut self, subscriptions: I, listener: F) -> &mut Self
+    where I: IntoIterator<Item=T>, T: Into<Subscription>, F: 'static + FnMut(&update::Update) {
+        let subscriptions = subscriptions.into_iter().map(Into::into).collect::<Vec<_>>();
+        self.config.require(subscriptions.iter().copied());
+        self.required.require(subscriptions);
+        self.add_listener(listener)
+    }
+
+    pub(crate) fn handle(&mut self, update: &update::Update) {
+        for subscription in self.required.update(update) {
+            log::warn!("a listener on service {} needs {:?}, but it hasn't been in any update yet", self.config.service_name(), subscription);
+        }
+        for callback in &mut self.listeners {
+            callback(update)
+        }
+    }
+}
+
+/// the update handler for each service, by name
+#[derive(Clone, StateData)]
+pub(crate) struct ServiceHandlers {
+    pub(crate) handlers: HashMap<String, UpdateHandler>,
+}
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub(crate) struct ServicePath {
+    name: String,
+}
+
+#[throws((State, HandlerError))]
+pub(crate) async fn handle_service_update(mut state: State) -> (State, Response<Body>) {
+    let name = ServicePath::take_from(&mut state).name;
+    let handler = ServiceHandlers::borrow_from(&state).handlers.get(&name).cloned();
+    match handler {
+        Some(handler) => {
+            // the main service's handler is replaced with this one's for the rest of the request
+            state.put(handler);
+            super::handle_update(state).await?
+        }
+        None => {
+            let response = create_empty_response(&state, StatusCode::NOT_FOUND);
+            (state, response)
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! updates streamed to browsers as Server-Sent Events, one event per section
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use gotham::helpers::http::response::create_response;
+use gotham::hyper::{header, Body, HeaderMap, Response, StatusCode};
+use gotham::hyper::body::Bytes;
+use gotham::state::{State, FromState};
+use tokio::sync::broadcast;
+
+use crate::update::Update;
+use super::state::StateStore;
+
+/// how often to send a comment, so idle connections aren't closed by proxies
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+struct Message {
+    id: u64,
+    event: String,
+    data: String,
+}
+
+impl Message {
+    fn to_bytes(&self) -> Bytes {
+        format!("id: {}\nevent: {}\ndata: {}\n\n", self.id, self.event, self.data).into()
+    }
+}
+
+struct Buffer {
+    next_id: u64,
+    messages: VecDeque<Arc<Message>>,
+    len: usize,
+    tx: broadcast::Sender<Arc<Message>>,
+}
+
+/// shared between the server, which feeds it, and every connected client
+#[derive(Clone, StateData)]
+pub(crate) struct EventStream {
+    inner: Arc<Mutex<Buffer>>,
+}
+
+impl EventStream {
+    pub(crate) fn new(len: usize) -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self {
+            inner: Arc::new(Mutex::new(Buffer {
+                next_id: 1,
+                messages: VecDeque::with_capacity(len),
+                len,
+                tx,
+            })),
+        }
+    }
+
+    pub(crate) fn send_update(&self, update: &Update) {
+        let mut buffer = self.inner.lock().expect("event stream poisoned");
+        for (section, value) in super::sections(update) {
+            let message = Arc::new(Message {
+                id: buffer.next_id,
+                event: section,
+                data: value.to_string(),
+            });
+            buffer.next_id += 1;
+            if buffer.len > 0 {
+                if buffer.messages.len() == buffer.len {
+                    buffer.messages.pop_front();
+                }
+                buffer.messages.push_back(message.clone());
+            }
+            // an error just means nobody's connected
+            let _ = buffer.tx.send(message);
+        }
+    }
+
+    /// the buffered messages after `last_id`, and a receiver for everything after those
+    fn subscribe(&self, last_id: Option<u64>) -> (Vec<Arc<Message>>, broadcast::Receiver<Arc<Message>>) {
+        let buffer = self.inner.lock().expect("event stream poisoned");
+        let missed = match last_id {
+            Some(last_id) => buffer.messages.iter()
+                .filter(|message| message.id > last_id)
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+        (missed, buffer.tx.subscribe())
+    }
+}
+
+pub(crate) fn get_events(state: State) -> (State, Response<Body>) {
+    let last_id = HeaderMap::borrow_from(&state)
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.trim().parse::<u64>().ok());
+    let (missed, mut rx) = EventStream::borrow_from(&state).subscribe(last_id);
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        for message in missed {
+            if sender.send_data(message.to_bytes()).await.is_err() {
+                return;
+            }
+        }
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE);
+        loop {
+            let chunk = tokio::select! {
+                received = rx.recv() => match received {
+                    Ok(message) => message.to_bytes(),
+                    Err(broadcast::RecvError::Lagged(skipped)) => {
+                        log::warn!("event stream client fell behind, skipping {} messages", skipped);
+                        continue;
+                    }
+                    Err(broadcast::RecvError::Closed) => return,
+                },
+                _ = keep_alive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+            };
+            if sender.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut response = create_response(&state, StatusCode::OK, mime::TEXT_EVENT_STREAM, body);
+    response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-cache"));
+    StateStore::borrow_from(&state).allow_origin(&mut response);
+    (state, response)
+}
@@ -0,0 +1,137 @@
+//! the latest update and recent history, served over HTTP for clients that can't register listeners
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use gotham::helpers::http::response::{create_empty_response, create_response};
+use gotham::hyper::{header, Body, Response, StatusCode};
+use gotham::state::{State, FromState};
+use serde::{Serialize, Deserialize};
+
+use crate::capture::CapturedUpdate;
+use crate::update::Update;
+
+/// an update, numbered in the order it was received
+#[derive(Clone, Serialize)]
+pub(crate) struct HistoryEntry {
+    pub(crate) id: u64,
+    pub(crate) received_at: u64,
+    pub(crate) update: Update,
+}
+
+struct Store {
+    next_id: u64,
+    latest: Option<HistoryEntry>,
+    history: VecDeque<HistoryEntry>,
+    history_len: usize,
+}
+
+/// shared between the update handler, which fills it, and the query endpoints, which read it
+#[derive(Clone, StateData)]
+pub(crate) struct StateStore {
+    inner: Arc<Mutex<Store>>,
+    allow_origin: Option<String>,
+}
+
+impl StateStore {
+    pub(crate) fn new(history_len: usize, allow_origin: Option<String>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Store {
+                next_id: 1,
+                latest: None,
+                history: VecDeque::with_capacity(history_len),
+                history_len,
+            })),
+            allow_origin,
+        }
+    }
+
+    pub(crate) fn push(&self, captured: &CapturedUpdate) -> HistoryEntry {
+        let mut store = self.inner.lock().expect("state store poisoned");
+        let entry = HistoryEntry {
+            id: store.next_id,
+            received_at: captured.received_at,
+            update: captured.update.clone(),
+        };
+        store.next_id += 1;
+        if store.history_len > 0 {
+            if store.history.len() == store.history_len {
+                store.history.pop_front();
+            }
+            store.history.push_back(entry.clone());
+        }
+        store.latest = Some(entry.clone());
+        entry
+    }
+
+    pub(crate) fn latest(&self) -> Option<HistoryEntry> {
+        self.inner.lock().expect("state store poisoned").latest.clone()
+    }
+
+    /// every entry still in the history with an ID greater than `since`
+    pub(crate) fn since(&self, since: u64) -> Vec<HistoryEntry> {
+        self.inner.lock().expect("state store poisoned")
+            .history.iter()
+            .filter(|entry| entry.id > since)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn allow_origin(&self, response: &mut Response<Body>) {
+        if let Some(origin) = &self.allow_origin {
+            if let Ok(origin) = origin.parse() {
+                response.headers_mut().insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            }
+        }
+    }
+}
+
+fn json_response<T: Serialize>(state: &State, value: Option<T>) -> Response<Body> {
+    let store = StateStore::borrow_from(state);
+    let mut response = match value.map(|value| serde_json::to_vec(&value)) {
+        Some(Ok(body)) => create_response(state, StatusCode::OK, mime::APPLICATION_JSON, body),
+        Some(Err(err)) => {
+            log::error!("failed to serialize state: {}", err);
+            create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        None => create_empty_response(state, StatusCode::NOT_FOUND),
+    };
+    store.allow_origin(&mut response);
+    response
+}
+
+pub(crate) fn get_state(state: State) -> (State, Response<Body>) {
+    let latest = StateStore::borrow_from(&state).latest();
+    let response = json_response(&state, latest.map(|entry| entry.update));
+    (state, response)
+}
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub(crate) struct SectionPath {
+    section: String,
+}
+
+pub(crate) fn get_state_section(mut state: State) -> (State, Response<Body>) {
+    let SectionPath { section } = SectionPath::take_from(&mut state);
+    let latest = StateStore::borrow_from(&state).latest().map(|entry| entry.update);
+    let response = match section.as_str() {
+        "map" => json_response(&state, latest.and_then(|update| update.map)),
+        "player" => json_response(&state, latest.and_then(|update| update.player)),
+        "provider" => json_response(&state, latest.and_then(|update| update.provider)),
+        "round" => json_response(&state, latest.and_then(|update| update.round)),
+        _ => json_response::<()>(&state, None),
+    };
+    (state, response)
+}
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub(crate) struct HistoryQuery {
+    since: Option<u64>,
+}
+
+pub(crate) fn get_history(mut state: State) -> (State, Response<Body>) {
+    let HistoryQuery { since } = HistoryQuery::take_from(&mut state);
+    let history = StateStore::borrow_from(&state).since(since.unwrap_or(0));
+    let response = json_response(&state, Some(history));
+    (state, response)
+}
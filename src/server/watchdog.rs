@@ -0,0 +1,58 @@
+//! notices when the game stops sending updates, or restarts
+
+use std::time::{Duration, Instant};
+
+use crate::events::Event;
+use crate::update::Update;
+
+pub(crate) struct Watchdog {
+    timeout: Duration,
+    last_update: Option<Instant>,
+    last_timestamp: Option<u64>,
+    connected: bool,
+}
+
+impl Watchdog {
+    pub(crate) fn new(heartbeat: Duration, grace: Duration) -> Self {
+        Self {
+            timeout: heartbeat + grace,
+            last_update: None,
+            last_timestamp: None,
+            connected: false,
+        }
+    }
+
+    /// how long to wait for the next update before the game counts as disconnected, if it's connected
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        match self.last_update {
+            Some(last_update) if self.connected => Some(self.timeout.saturating_sub(last_update.elapsed())),
+            _ => None,
+        }
+    }
+
+    /// note that an update arrived, returning whether that means the game (re)connected or restarted
+    pub(crate) fn update(&mut self, update: &Update) -> Vec<Event> {
+        let mut events = vec![];
+        self.last_update = Some(Instant::now());
+        if !self.connected {
+            self.connected = true;
+            events.push(Event::GameConnected);
+        }
+        if let Some(provider) = &update.provider {
+            if self.last_timestamp.is_some_and(|last_timestamp| provider.timestamp < last_timestamp) {
+                events.push(Event::GameRestarted);
+            }
+            self.last_timestamp = Some(provider.timestamp);
+        }
+        events
+    }
+
+    /// check whether the game has gone quiet for too long
+    pub(crate) fn check(&mut self) -> Option<Event> {
+        if self.connected && self.remaining() == Some(Duration::from_secs(0)) {
+            self.connected = false;
+            return Some(Event::GameDisconnected);
+        }
+        None
+    }
+}
@@ -0,0 +1,173 @@
+//! updates and events, pushed to WebSocket clients as they arrive
+
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use gotham::helpers::http::response::create_empty_response;
+use gotham::hyper::{Body, HeaderMap, Method, Request, Response, StatusCode, Version};
+use gotham::hyper::upgrade::Upgraded;
+use gotham::state::{State, FromState};
+use serde::{Serialize, Deserialize};
+use serde_json::{Map as JsonMap, Value};
+use tokio::sync::broadcast;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::handshake::server::create_response;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::events::Event;
+use crate::update::Update;
+
+#[derive(Clone)]
+enum Broadcast {
+    Update(Arc<Update>),
+    Event(Arc<Event>),
+}
+
+/// shared between the server, which feeds it, and every connected client
+#[derive(StateData)]
+pub(crate) struct WebSocketHub {
+    // a panicking client can't leave the channel inconsistent, since sending and subscribing are
+    // all it's used for
+    tx: AssertUnwindSafe<broadcast::Sender<Broadcast>>,
+    events: bool,
+}
+
+impl Clone for WebSocketHub {
+    fn clone(&self) -> Self {
+        Self {
+            tx: AssertUnwindSafe(self.tx.clone()),
+            events: self.events,
+        }
+    }
+}
+
+impl WebSocketHub {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self {
+            tx: AssertUnwindSafe(tx),
+            events: false,
+        }
+    }
+
+    pub(crate) fn send_events(&mut self, events: bool) {
+        self.events = events;
+    }
+
+    fn send(&self, broadcast: Broadcast) {
+        // an error just means nobody's connected
+        let _ = self.tx.send(broadcast);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Broadcast> {
+        self.tx.subscribe()
+    }
+
+    pub(crate) fn send_update(&self, update: &Update) {
+        self.send(Broadcast::Update(Arc::new(update.clone())));
+    }
+
+    pub(crate) fn send_event(&self, event: &Event) {
+        if self.events {
+            self.send(Broadcast::Event(Arc::new(event.clone())));
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Outgoing<'a> {
+    Update { update: JsonMap<String, Value> },
+    Event { event: &'a Event },
+}
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub(crate) struct TopicQuery {
+    topics: Option<String>,
+}
+
+impl TopicQuery {
+    fn includes(&self, topic: &str) -> bool {
+        match &self.topics {
+            Some(topics) => topics.split(',').any(|wanted| wanted.trim() == topic),
+            None => true,
+        }
+    }
+
+    /// the message to send for the given broadcast, if the client wants it
+    fn message(&self, broadcast: &Broadcast) -> Option<String> {
+        let outgoing = match broadcast {
+            Broadcast::Update(update) => {
+                let update = super::sections(update).into_iter()
+                    .filter(|(section, _)| self.includes(section))
+                    .collect::<JsonMap<_, _>>();
+                if update.is_empty() {
+                    return None;
+                }
+                Outgoing::Update { update }
+            }
+            Broadcast::Event(event) if self.includes(event.topic()) => Outgoing::Event { event },
+            Broadcast::Event(_) => return None,
+        };
+        serde_json::to_string(&outgoing)
+            .map_err(|err| log::error!("failed to serialize WebSocket message: {}", err))
+            .ok()
+    }
+}
+
+async fn serve(mut socket: WebSocketStream<Upgraded>, mut rx: broadcast::Receiver<Broadcast>, query: TopicQuery) {
+    loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Ok(broadcast) => {
+                    if let Some(message) = query.message(&broadcast) {
+                        if socket.send(Message::Text(message)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(broadcast::RecvError::Lagged(skipped)) => {
+                    log::warn!("WebSocket client fell behind, skipping {} messages", skipped);
+                }
+                Err(broadcast::RecvError::Closed) => break,
+            },
+            incoming = socket.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+pub(crate) fn handle_websocket(mut state: State) -> (State, Response<Body>) {
+    let query = TopicQuery::take_from(&mut state);
+    let mut request = Request::new(());
+    *request.method_mut() = Method::borrow_from(&state).clone();
+    *request.version_mut() = *Version::borrow_from(&state);
+    *request.headers_mut() = HeaderMap::borrow_from(&state).clone();
+    let handshake = match create_response(&request) {
+        Ok(handshake) => handshake,
+        Err(err) => {
+            log::warn!("rejected WebSocket connection: {}", err);
+            let response = create_empty_response(&state, StatusCode::BAD_REQUEST);
+            return (state, response);
+        }
+    };
+
+    let body = Body::take_from(&mut state);
+    let rx = WebSocketHub::borrow_from(&state).subscribe();
+    tokio::spawn(async move {
+        match body.on_upgrade().await {
+            Ok(upgraded) => {
+                let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                serve(socket, rx, query).await;
+            }
+            Err(err) => log::warn!("WebSocket upgrade failed: {}", err),
+        }
+    });
+
+    let (parts, ()) = handshake.into_parts();
+    (state, Response::from_parts(parts, Body::empty()))
+}
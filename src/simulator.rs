@@ -0,0 +1,564 @@
+//! synthetic GSI traffic, for testing listeners without launching the game
+//!
+//! ```
+//! use csgo_gsi::{GSIConfigBuilder, Subscription};
+//! use csgo_gsi::simulator::{MatchScript, RoundEnd, Simulator};
+//! use csgo_gsi::update::Team;
+//!
+//! let config = GSIConfigBuilder::new("csgo-gsi Example")
+//!     .subscribe_multiple(Subscription::UNRESTRICTED)
+//!     .try_build()
+//!     .expect("config wasn't valid");
+//! let script = MatchScript::new("de_dust2")
+//!     .round(Team::CT, RoundEnd::Elimination)
+//!     .round(Team::T, RoundEnd::BombExploded);
+//! let captures = Simulator::new(&config, script)
+//!     .expect("script should be valid")
+//!     .captures()
+//!     .expect("simulated updates should parse");
+//! assert!(!captures.is_empty());
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use fehler::{throws, throw};
+use serde_json::{json, Map as JsonMap, Value};
+
+use crate::{Error, GSIConfig, Subscription};
+use crate::capture::CapturedUpdate;
+use crate::replay::Replayer;
+use crate::update::{Team, Update};
+
+const WARMUP: Duration = Duration::from_secs(30);
+const FREEZE_TIME: Duration = Duration::from_secs(15);
+const ROUND_TIME: Duration = Duration::from_secs(115);
+const PLANT_AFTER: Duration = Duration::from_secs(40);
+const BOMB_TIMER: Duration = Duration::from_secs(40);
+const DEFUSE_AFTER: Duration = Duration::from_secs(30);
+const ELIMINATION_AFTER: Duration = Duration::from_secs(50);
+const SURRENDER_AFTER: Duration = Duration::from_secs(10);
+const ROUND_OVER: Duration = Duration::from_secs(7);
+
+const START_TIMESTAMP: u64 = 1_600_000_000;
+const STEAM_ID: &str = "76561197960265728";
+
+/// how a round ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundEnd {
+    /// the winners killed everyone on the other team
+    Elimination,
+    /// the bomb exploded (terrorists win)
+    BombExploded,
+    /// the bomb was defused (counter-terrorists win)
+    Defused,
+    /// the round timer ran out (counter-terrorists win)
+    TimeRanOut,
+    /// the losers surrendered, ending the match
+    Surrender,
+}
+
+impl RoundEnd {
+    fn reason(self) -> &'static str {
+        match self {
+            RoundEnd::Elimination => "elimination",
+            RoundEnd::BombExploded => "bomb",
+            RoundEnd::Defused => "defuse",
+            RoundEnd::TimeRanOut => "time",
+            RoundEnd::Surrender => "surrender",
+        }
+    }
+
+    fn required_winner(self) -> Option<Team> {
+        match self {
+            RoundEnd::BombExploded => Some(Team::T),
+            RoundEnd::Defused | RoundEnd::TimeRanOut => Some(Team::CT),
+            RoundEnd::Elimination | RoundEnd::Surrender => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ScriptedRound {
+    winner: Team,
+    end: RoundEnd,
+    bomb_planted: bool,
+}
+
+/// a scripted match, as a sequence of round outcomes
+///
+/// the local player is always on the team that starts as counter-terrorists.
+#[derive(Clone, Debug)]
+pub struct MatchScript {
+    map_name: String,
+    max_rounds: u64,
+    overtime_max_rounds: u64,
+    rounds: Vec<ScriptedRound>,
+}
+
+impl MatchScript {
+    /// start a competitive match script on the given map
+    pub fn new<S: Into<String>>(map_name: S) -> Self {
+        Self {
+            map_name: map_name.into(),
+            max_rounds: 30,
+            overtime_max_rounds: 6,
+            rounds: vec![],
+        }
+    }
+
+    /// maximum rounds in regulation time (default is 30)
+    pub fn max_rounds(mut self, max_rounds: u64) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// maximum rounds in each overtime (default is 6)
+    pub fn overtime_max_rounds(mut self, overtime_max_rounds: u64) -> Self {
+        self.overtime_max_rounds = overtime_max_rounds;
+        self
+    }
+
+    /// add a round won by the given side, planting the bomb if the round end requires it
+    pub fn round(self, winner: Team, end: RoundEnd) -> Self {
+        let bomb_planted = end == RoundEnd::BombExploded || end == RoundEnd::Defused;
+        self.push_round(winner, end, bomb_planted)
+    }
+
+    /// add a round won by the given side, in which the bomb was planted
+    pub fn planted_round(self, winner: Team, end: RoundEnd) -> Self {
+        self.push_round(winner, end, true)
+    }
+
+    /// add several identical rounds
+    pub fn rounds(mut self, count: usize, winner: Team, end: RoundEnd) -> Self {
+        for _ in 0..count {
+            self = self.round(winner, end);
+        }
+        self
+    }
+
+    fn push_round(mut self, winner: Team, end: RoundEnd, bomb_planted: bool) -> Self {
+        self.rounds.push(ScriptedRound { winner, end, bomb_planted });
+        self
+    }
+}
+
+/// the simulated game state, with the local player's team as team 0
+struct GameState {
+    time: Duration,
+    map_name: String,
+    map_phase: &'static str,
+    map_round: u64,
+    round_phase: &'static str,
+    bomb: Option<&'static str>,
+    win_team: Option<Team>,
+    scores: [u64; 2],
+    consecutive_losses: [u64; 2],
+    round_wins: Vec<(u64, String)>,
+    local_is_ct: bool,
+    health: u64,
+    money: u64,
+    kills: i64,
+    deaths: u64,
+}
+
+impl GameState {
+    fn side_of(&self, team: usize) -> Team {
+        if (team == 0) == self.local_is_ct {
+            Team::CT
+        } else {
+            Team::T
+        }
+    }
+
+    fn team_on(&self, side: Team) -> usize {
+        if self.side_of(0) == side {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn side_name(side: Team) -> &'static str {
+        match side {
+            Team::CT => "ct",
+            Team::T => "t",
+        }
+    }
+
+    fn team_json(&self, team: usize) -> Value {
+        json!({
+            "score": self.scores[team],
+            "consecutive_round_losses": self.consecutive_losses[team],
+            "timeouts_remaining": 1,
+            "matches_won_this_series": 0,
+        })
+    }
+
+    fn to_json(&self, subscriptions: &HashSet<Subscription>, auth: &HashMap<String, String>) -> Value {
+        let mut root = JsonMap::new();
+        if subscriptions.contains(&Subscription::Provider) {
+            root.insert("provider".into(), json!({
+                "name": "Counter-Strike: Global Offensive",
+                "appid": 730,
+                "version": 13768,
+                "steamid": STEAM_ID,
+                "timestamp": START_TIMESTAMP + self.time.as_secs(),
+            }));
+        }
+        if subscriptions.contains(&Subscription::Map) {
+            let mut map = json!({
+                "mode": "competitive",
+                "name": self.map_name,
+                "phase": self.map_phase,
+                "round": self.map_round,
+                "team_ct": self.team_json(self.team_on(Team::CT)),
+                "team_t": self.team_json(self.team_on(Team::T)),
+                "num_matches_to_win_series": 0,
+                "current_spectators": 0,
+                "souvenirs_total": 0,
+            });
+            if subscriptions.contains(&Subscription::MapRoundWins) && !self.round_wins.is_empty() {
+                let round_wins = self.round_wins.iter()
+                    .map(|(round, win)| (round.to_string(), Value::from(win.as_str())))
+                    .collect::<JsonMap<_, _>>();
+                map["round_wins"] = Value::Object(round_wins);
+            }
+            root.insert("map".into(), map);
+        }
+        if subscriptions.contains(&Subscription::Round) {
+            let mut round = json!({ "phase": self.round_phase });
+            if let Some(bomb) = self.bomb {
+                round["bomb"] = bomb.into();
+            }
+            if let Some(win_team) = self.win_team {
+                round["win_team"] = serde_json::to_value(win_team).expect("teams always serialize");
+            }
+            root.insert("round".into(), round);
+        }
+        let player_subscriptions = [
+            Subscription::PlayerID,
+            Subscription::PlayerMatchStats,
+            Subscription::PlayerState,
+            Subscription::PlayerWeapons,
+        ];
+        if player_subscriptions.iter().any(|x| subscriptions.contains(x)) {
+            let mut player = json!({
+                "steamid": STEAM_ID,
+                "name": "simulated player",
+                "observer_slot": 1,
+                "team": serde_json::to_value(self.side_of(0)).expect("teams always serialize"),
+                "activity": "playing",
+            });
+            if subscriptions.contains(&Subscription::PlayerState) {
+                player["state"] = json!({
+                    "health": self.health,
+                    "armor": if self.health > 0 { 100 } else { 0 },
+                    "helmet": self.health > 0,
+                    "flashed": 0,
+                    "smoked": 0,
+                    "burning": 0,
+                    "money": self.money,
+                    "round_kills": 0,
+                    "round_killhs": 0,
+                    "round_totaldmg": 0,
+                    "equip_value": if self.health > 0 { 1000 } else { 0 },
+                });
+            }
+            if subscriptions.contains(&Subscription::PlayerMatchStats) {
+                player["match_stats"] = json!({
+                    "kills": self.kills,
+                    "assists": 0,
+                    "deaths": self.deaths,
+                    "mvps": 0,
+                    "score": self.kills * 2,
+                });
+            }
+            if subscriptions.contains(&Subscription::PlayerWeapons) && self.health > 0 {
+                let pistol = if self.local_is_ct { "weapon_usp_silencer" } else { "weapon_glock" };
+                player["weapons"] = json!({
+                    "weapon_0": {
+                        "name": "weapon_knife",
+                        "paintkit": "default",
+                        "type": "Knife",
+                        "state": "holstered",
+                    },
+                    "weapon_1": {
+                        "name": pistol,
+                        "paintkit": "default",
+                        "type": "Pistol",
+                        "ammo_clip": 12,
+                        "ammo_clip_max": 12,
+                        "ammo_reserve": 24,
+                        "state": "active",
+                    },
+                });
+            }
+            root.insert("player".into(), player);
+        }
+        root.insert("auth".into(), json!(auth));
+        Value::Object(root)
+    }
+}
+
+/// record how `current` differs from `previous`, the way CS:GO does in `previously` and `added`
+fn diff(previous: &JsonMap<String, Value>, current: &JsonMap<String, Value>, previously: &mut JsonMap<String, Value>, added: &mut JsonMap<String, Value>) {
+    for (key, old) in previous {
+        match (old, current.get(key)) {
+            (_, None) => {
+                previously.insert(key.clone(), old.clone());
+            }
+            (Value::Object(old), Some(Value::Object(new))) => {
+                let mut inner_previously = JsonMap::new();
+                let mut inner_added = JsonMap::new();
+                diff(old, new, &mut inner_previously, &mut inner_added);
+                if !inner_previously.is_empty() {
+                    previously.insert(key.clone(), Value::Object(inner_previously));
+                }
+                if !inner_added.is_empty() {
+                    added.insert(key.clone(), Value::Object(inner_added));
+                }
+            }
+            (old, Some(new)) if old != new => {
+                previously.insert(key.clone(), old.clone());
+            }
+            _ => {}
+        }
+    }
+    for key in current.keys() {
+        if !previous.contains_key(key) {
+            added.insert(key.clone(), Value::Bool(true));
+        }
+    }
+}
+
+/// generates the updates CS:GO would send while playing a scripted match
+pub struct Simulator {
+    buffer: Duration,
+    throttle: Duration,
+    heartbeat: Duration,
+    auth: HashMap<String, String>,
+    subscriptions: HashSet<Subscription>,
+    script: MatchScript,
+}
+
+impl Simulator {
+    /// simulate the given script, sending updates as the given configuration asks
+    #[throws]
+    pub fn new<S>(config: &GSIConfig<S>, script: MatchScript) -> Self {
+        let is_valid = |rounds: u64| rounds > 0 && rounds.is_multiple_of(2);
+        if !is_valid(script.max_rounds) || !is_valid(script.overtime_max_rounds) {
+            throw!(Error::SimulatorError { description: "max rounds must be even and nonzero", cause: None });
+        }
+        for round in &script.rounds {
+            if let Some(required) = round.end.required_winner() {
+                if required != round.winner {
+                    throw!(Error::SimulatorError { description: "round end is not possible for the scripted winner", cause: None });
+                }
+            }
+        }
+        Self {
+            buffer: config.buffer(),
+            throttle: config.throttle(),
+            heartbeat: config.heartbeat(),
+            auth: config.auth().clone(),
+            subscriptions: config.subscriptions().clone(),
+            script,
+        }
+    }
+
+    fn is_match_over(&self, state: &GameState) -> bool {
+        let max_rounds = self.script.max_rounds;
+        let overtime = self.script.overtime_max_rounds;
+        let played = state.map_round;
+        let leader = state.scores[0].max(state.scores[1]);
+        let target = if played <= max_rounds {
+            max_rounds / 2 + 1
+        } else {
+            let overtimes_done = (played - max_rounds - 1) / overtime;
+            max_rounds / 2 + overtimes_done * overtime / 2 + overtime / 2 + 1
+        };
+        leader >= target
+    }
+
+    fn swaps_sides_after(&self, played: u64) -> bool {
+        let max_rounds = self.script.max_rounds;
+        let overtime = self.script.overtime_max_rounds;
+        if played <= max_rounds {
+            played == max_rounds / 2
+        } else {
+            (played - max_rounds) % overtime == overtime / 2
+        }
+    }
+
+    /// every change to the game state, and when it happened
+    fn events(&self) -> Vec<(Duration, Value)> {
+        let mut events = vec![];
+        let mut state = GameState {
+            time: Duration::from_secs(0),
+            map_name: self.script.map_name.clone(),
+            map_phase: "warmup",
+            map_round: 0,
+            round_phase: "live",
+            bomb: None,
+            win_team: None,
+            scores: [0, 0],
+            consecutive_losses: [0, 0],
+            round_wins: vec![],
+            local_is_ct: true,
+            health: 100,
+            money: 800,
+            kills: 0,
+            deaths: 0,
+        };
+        let mut push = |state: &GameState| events.push((state.time, state.to_json(&self.subscriptions, &self.auth)));
+        push(&state);
+        state.time += WARMUP;
+
+        let mut halftime = false;
+        for round in &self.script.rounds {
+            state.map_phase = if halftime { "intermission" } else { "live" };
+            state.round_phase = "freezetime";
+            state.bomb = None;
+            state.win_team = None;
+            state.health = 100;
+            push(&state);
+            state.time += FREEZE_TIME;
+
+            state.map_phase = "live";
+            state.round_phase = "live";
+            push(&state);
+            let live_start = state.time;
+
+            let plant_time = live_start + PLANT_AFTER;
+            if round.bomb_planted {
+                state.time = plant_time;
+                state.bomb = Some("planted");
+                push(&state);
+            }
+
+            state.time = match round.end {
+                RoundEnd::Elimination if round.bomb_planted => plant_time + BOMB_TIMER / 2,
+                RoundEnd::Elimination => live_start + ELIMINATION_AFTER,
+                RoundEnd::BombExploded => plant_time + BOMB_TIMER,
+                RoundEnd::Defused => plant_time + DEFUSE_AFTER,
+                RoundEnd::TimeRanOut => live_start + ROUND_TIME,
+                RoundEnd::Surrender => live_start + SURRENDER_AFTER,
+            };
+            match round.end {
+                RoundEnd::BombExploded => state.bomb = Some("exploded"),
+                RoundEnd::Defused => state.bomb = Some("defused"),
+                _ => {}
+            }
+            let winner = state.team_on(round.winner);
+            let loser = 1 - winner;
+            state.round_phase = "over";
+            state.win_team = Some(round.winner);
+            state.scores[winner] += 1;
+            state.consecutive_losses[winner] = 0;
+            state.consecutive_losses[loser] += 1;
+            state.map_round += 1;
+            state.round_wins.push((state.map_round, format!("{}_win_{}", GameState::side_name(round.winner), round.end.reason())));
+            if winner == 0 {
+                state.kills += 1;
+                state.money += 3250;
+            } else {
+                state.health = 0;
+                state.deaths += 1;
+                state.money += 1400 + 500 * (state.consecutive_losses[0] - 1).min(4);
+            }
+            state.money = state.money.min(16000);
+
+            let match_over = round.end == RoundEnd::Surrender || self.is_match_over(&state);
+            if match_over {
+                state.map_phase = "gameover";
+            }
+            push(&state);
+            if match_over {
+                break;
+            }
+            state.time += ROUND_OVER;
+
+            halftime = self.swaps_sides_after(state.map_round);
+            if halftime {
+                state.local_is_ct = !state.local_is_ct;
+                state.money = 800;
+            }
+        }
+        events
+    }
+
+    /// the updates CS:GO would send, timed according to the configured buffer, throttle and heartbeat
+    #[throws]
+    pub fn captures(&self) -> Vec<CapturedUpdate> {
+        let events = self.events();
+        let mut captures = vec![];
+        let mut sent: Option<(Duration, JsonMap<String, Value>)> = None;
+        let mut next = 0;
+        while next < events.len() {
+            let ready_at = events[next].0 + self.buffer;
+            if let Some((last_sent, state)) = &sent {
+                let heartbeat_at = *last_sent + self.heartbeat;
+                if heartbeat_at < ready_at {
+                    let state = state.clone();
+                    captures.push(Self::capture(heartbeat_at, state.clone(), None)?);
+                    sent = Some((heartbeat_at, state));
+                    continue;
+                }
+            }
+            let send_at = match &sent {
+                Some((last_sent, _)) => ready_at.max(*last_sent + self.throttle),
+                None => ready_at,
+            };
+            let mut current = JsonMap::new();
+            while next < events.len() && events[next].0 <= send_at {
+                if let Value::Object(state) = &events[next].1 {
+                    current = state.clone();
+                }
+                next += 1;
+            }
+            let previous = sent.as_ref().map(|(_, previous)| previous);
+            captures.push(Self::capture(send_at, current.clone(), previous)?);
+            sent = Some((send_at, current));
+        }
+        captures
+    }
+
+    /// a replayer for the simulated updates
+    #[throws]
+    pub fn replayer(&self) -> Replayer {
+        Replayer::from_captures(self.captures()?)
+    }
+
+    #[throws]
+    fn capture(at: Duration, state: JsonMap<String, Value>, previous: Option<&JsonMap<String, Value>>) -> CapturedUpdate {
+        let mut body = state.clone();
+        if let Some(previous) = previous {
+            let strip = |state: &JsonMap<String, Value>| {
+                let mut state = state.clone();
+                state.remove("provider");
+                state.remove("auth");
+                state
+            };
+            let mut previously = JsonMap::new();
+            let mut added = JsonMap::new();
+            diff(&strip(previous), &strip(&state), &mut previously, &mut added);
+            if !previously.is_empty() {
+                body.insert("previously".into(), Value::Object(previously));
+            }
+            if !added.is_empty() {
+                body.insert("added".into(), Value::Object(added));
+            }
+        }
+        let body = Value::Object(body);
+        let raw = body.to_string();
+        let update = serde_json::from_value::<Update>(body)
+            .map_err(|err| Error::SimulatorError { description: "simulated update did not parse", cause: Some(Box::new(err)) })?;
+        CapturedUpdate {
+            received_at: START_TIMESTAMP * 1000 + at.as_millis() as u64,
+            raw,
+            update,
+        }
+    }
+}
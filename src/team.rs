@@ -0,0 +1,138 @@
+//! several players' games sending updates to one server, merged into one view of their team
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+use crate::update::{Team, Update, map::Map, player::Weapon, round::Round};
+
+/// which game an update came from: the steam ID of whoever's playing it, and the auth it sent
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct ClientId {
+    /// the provider's steam ID
+    pub steam_id: String,
+    /// the auth key/value pairs sent with the update
+    pub auth: BTreeMap<String, String>,
+}
+
+impl ClientId {
+    /// the client an update came from, or `None` if it has no provider info
+    pub fn of(update: &Update) -> Option<Self> {
+        let provider = update.provider.as_ref()?;
+        Some(Self {
+            steam_id: provider.steam_id.clone(),
+            auth: update.auth.iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        })
+    }
+}
+
+/// what one team member's game last said about them
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Member {
+    /// display name
+    pub name: String,
+    /// team
+    pub team: Option<Team>,
+    /// health, or 0 if dead
+    pub health: u64,
+    /// armor
+    pub armor: u64,
+    /// money
+    pub money: u64,
+    /// weapon inventory
+    pub weapons: HashMap<String, Weapon>,
+}
+
+impl Member {
+    /// whether this member is still alive
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+}
+
+/// the state of a whole team, merged from each member's first-person updates
+///
+/// feed it from [`GSIServer::add_client_listener`](../struct.GSIServer.html#method.add_client_listener).
+/// members' own games only know about them while they're alive, so a member who's dead and
+/// spectating someone else keeps what was last known about them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TeamView {
+    members: BTreeMap<ClientId, Member>,
+    map: Option<Map>,
+    round: Option<Round>,
+}
+
+impl TeamView {
+    /// create a view with no members yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// merge in an update from the given client
+    pub fn update(&mut self, client: &ClientId, update: &Update) {
+        if update.map.is_some() {
+            self.map = update.map.clone();
+        }
+        if update.round.is_some() {
+            self.round = update.round.clone();
+        }
+        let player = match &update.player {
+            Some(player) if player.steam_id == client.steam_id => player,
+            // spectating someone else, who may not even be on this team
+            _ => return,
+        };
+        let member = self.members.entry(client.clone()).or_insert_with(|| Member {
+            name: player.name.clone(),
+            team: None,
+            health: 0,
+            armor: 0,
+            money: 0,
+            weapons: HashMap::new(),
+        });
+        member.name = player.name.clone();
+        member.team = player.team.or(member.team);
+        if let Some(state) = &player.state {
+            member.health = state.health;
+            member.armor = state.armor;
+            member.money = state.money;
+        }
+        member.weapons = player.weapons.clone();
+    }
+
+    /// forget a client, e.g. because it disconnected
+    pub fn remove(&mut self, client: &ClientId) -> Option<Member> {
+        self.members.remove(client)
+    }
+
+    /// every member, by the client their updates come from
+    pub fn members(&self) -> &BTreeMap<ClientId, Member> {
+        &self.members
+    }
+
+    /// the members on the given team
+    pub fn team(&self, team: Team) -> impl Iterator<Item=&Member> {
+        self.members.values().filter(move |member| member.team == Some(team))
+    }
+
+    /// the latest map info any member's game sent
+    pub fn map(&self) -> Option<&Map> {
+        self.map.as_ref()
+    }
+
+    /// the latest round info any member's game sent
+    pub fn round(&self) -> Option<&Round> {
+        self.round.as_ref()
+    }
+
+    /// how much money the given team has between them
+    pub fn total_money(&self, team: Team) -> u64 {
+        self.team(team).map(|member| member.money).sum()
+    }
+
+    /// how many of the given team are still alive
+    pub fn alive(&self, team: Team) -> usize {
+        self.team(team).filter(|member| member.is_alive()).count()
+    }
+}
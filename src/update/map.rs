@@ -55,7 +55,7 @@ pub enum Mode {
 }
 
 /// map phase
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Phase {
     /// warmup
@@ -70,9 +70,30 @@ pub enum Phase {
 
 /// information about who won and how
 /// ("ct_win_time", "t_win_bomb", "ct_win_elimination", "ct_win_defuse", etc)
-/// (TODO actually parse)
+///
+/// use [`parse_round_win`](fn.parse_round_win.html) to split it up.
 pub type RoundWin = String;
 
+/// the team that won a round, and how (e.g. `"time"`, `"bomb"`, `"elimination"`, `"defuse"`)
+pub fn parse_round_win(win: &str) -> Option<(super::Team, &str)> {
+    if let Some(reason) = win.strip_prefix("ct_win_") {
+        Some((super::Team::CT, reason))
+    } else {
+        win.strip_prefix("t_win_").map(|reason| (super::Team::T, reason))
+    }
+}
+
+impl Map {
+    /// each round's winner and how they won, in order, skipping any that couldn't be parsed
+    pub fn parsed_round_wins(&self) -> Vec<(u64, super::Team, &str)> {
+        let mut wins = self.round_wins.iter()
+            .filter_map(|(round, win)| parse_round_win(win).map(|(team, reason)| (*round, team, reason)))
+            .collect::<Vec<_>>();
+        wins.sort_by_key(|(round, _, _)| *round);
+        wins
+    }
+}
+
 /// team info
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -2,7 +2,11 @@
 
 use std::collections::HashMap;
 
-use serde::{Serialize, Deserialize, de::IgnoredAny};
+use fehler::throws;
+use serde::{Serialize, Deserialize, de::{DeserializeOwned, IgnoredAny}};
+use serde_path_to_error::Segment;
+
+use crate::Error;
 
 pub mod player;
 use player::Player;
@@ -13,10 +17,10 @@ use map::Map;
 pub mod round;
 use round::Round;
 
-// TODO abuse generics to align subscriptions with these types
+pub mod typed;
 
 /// a team
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Team {
     /// counter-terrorists
     CT,
@@ -38,12 +42,58 @@ pub struct Update {
     pub auth: HashMap<String, String>,
     /// round info
     pub round: Option<Round>,
+    #[allow(dead_code)]
     #[serde(skip_serializing, default)]
     added: IgnoredAny,
+    #[allow(dead_code)]
     #[serde(skip_serializing, default)]
     previously: IgnoredAny,
 }
 
+impl Update {
+    /// parse an update from a raw request body, reporting exactly where it went wrong if it doesn't parse
+    #[throws]
+    pub fn from_json(raw: &str) -> Self {
+        parse(raw)?
+    }
+}
+
+/// parse an update (typed or not), reporting exactly where it went wrong if it doesn't parse
+#[throws]
+fn parse<T: DeserializeOwned>(raw: &str) -> T {
+    parse_value(&parse_json(raw)?, raw)?
+}
+
+/// parse a raw request body as JSON, without checking its structure yet
+#[throws]
+pub(crate) fn parse_json(raw: &str) -> serde_json::Value {
+    serde_json::from_str(raw).map_err(|cause| Error::InvalidJson { raw: raw.to_string(), cause })?
+}
+
+/// parse an update (typed or not) from the JSON in a raw request body
+#[throws]
+pub(crate) fn parse_value<T: DeserializeOwned>(json_value: &serde_json::Value, raw: &str) -> T {
+    serde_path_to_error::deserialize::<_, T>(json_value)
+        .map_err(|err| {
+            let path = json_pointer(err.path());
+            let value = json_value.pointer(&path).cloned();
+            Error::UpdateSchema { path, value, raw: raw.to_string(), cause: err.into_inner() }
+        })?
+}
+
+/// format a serde path as a JSON pointer, like `/player/weapons/weapon_3/type`
+fn json_pointer(path: &serde_path_to_error::Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(index.to_string()),
+            Segment::Map { key } => Some(key.clone()),
+            Segment::Enum { variant } => Some(variant.clone()),
+            Segment::Unknown => None,
+        })
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
 /// information about the GSI info provider (CS:GO itself)
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -68,6 +118,7 @@ mod rhai_package {
     use std::fmt::Display;
     use rhai::{
         def_package,
+        Array,
         Dynamic,
         ImmutableString,
         Map as RhaiMap,
@@ -253,8 +304,201 @@ mod rhai_package {
         make_stringable!(round::Phase as Debug);
 
         make_stringable!(round::BombState as Debug);
+
+        // helpers, so scripts don't have to dig through optional sections themselves
+        module.set_fn_1_mut("is_alive", |x: &mut Player| Ok(x.state.as_ref().is_some_and(|state| state.health > 0)));
+        module.set_fn_1_mut("is_alive", |x: &mut player::State| Ok(x.health > 0));
+        module.set_fn_1_mut("active_weapon", |x: &mut Player| {
+            let active = x.weapons.values()
+                .find(|weapon| matches!(weapon.state, player::WeaponState::Active | player::WeaponState::Reloading));
+            Ok(active.cloned().into_dynamic())
+        });
+        module.set_fn_2_mut("has_weapon_type", |x: &mut Player, name: ImmutableString| {
+            Ok(x.weapons.values().any(|weapon| {
+                weapon.r#type.as_ref().is_some_and(|r#type| format!("{:?}", r#type).eq_ignore_ascii_case(&name))
+            }))
+        });
+        module.set_fn_1_mut("team_of", |x: &mut Update| Ok(x.player.as_ref().and_then(|player| player.team).into_dynamic()));
+        module.set_fn_1_mut("team_of", |x: &mut Player| Ok(x.team.into_dynamic()));
+        module.set_fn_1_mut("parsed_round_wins", |x: &mut Map| {
+            let wins = x.parsed_round_wins().into_iter()
+                .map(|(round, team, reason)| {
+                    let mut win = RhaiMap::new();
+                    win.insert("round".into(), Dynamic::from(round));
+                    win.insert("team".into(), Dynamic::from(team));
+                    win.insert("reason".into(), Dynamic::from(ImmutableString::from(reason)));
+                    Dynamic::from(win)
+                })
+                .collect::<Array>();
+            Ok(wins)
+        });
     });
 }
 
 #[cfg(feature = "rhai")]
 pub use rhai_package::CSGOPackage;
+
+#[cfg(feature = "lua")]
+mod lua_package {
+    use std::fmt::Debug;
+    use mlua::{AnyUserData, Lua, MetaMethod, Result as LuaResult, UserData, UserDataFields, UserDataMethods};
+    use super::*;
+
+    /// enums are passed to Lua as their names
+    pub(crate) fn name<T: Debug>(x: &T) -> String {
+        format!("{:?}", x)
+    }
+
+    macro_rules! userdata {
+        ($ty:ty { $($name:literal: |$this:ident| $get:expr),* $(,)? }) => {
+            impl UserData for $ty {
+                fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+                    $(fields.add_field_method_get($name, |_, $this| Ok($get));)*
+                }
+
+                fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+                    methods.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(format!("{:?}", this)));
+                }
+            }
+        };
+    }
+
+    userdata!(Update {
+        "map": |x| x.map.clone(),
+        "player": |x| x.player.clone(),
+        "provider": |x| x.provider.clone(),
+        "round": |x| x.round.clone(),
+    });
+
+    userdata!(Provider {
+        "name": |x| x.name.clone(),
+        "app_id": |x| x.app_id,
+        "version": |x| x.version,
+        "steam_id": |x| x.steam_id.clone(),
+        "timestamp": |x| x.timestamp,
+    });
+
+    userdata!(Map {
+        "current_spectators": |x| x.current_spectators,
+        "mode": |x| name(&x.mode),
+        "name": |x| x.name.clone(),
+        "num_matches_to_win_series": |x| x.num_matches_to_win_series,
+        "phase": |x| name(&x.phase),
+        "round": |x| x.round,
+        "round_wins": |x| x.round_wins.clone(),
+        "souvenirs_total": |x| x.souvenirs_total,
+        "team_ct": |x| x.team_ct.clone(),
+        "team_t": |x| x.team_t.clone(),
+    });
+
+    userdata!(map::Team {
+        "score": |x| x.score,
+        "consecutive_round_losses": |x| x.consecutive_round_losses,
+        "timeouts_remaining": |x| x.timeouts_remaining,
+        "matches_won_this_series": |x| x.matches_won_this_series,
+        "name": |x| x.name.clone(),
+        "flag": |x| x.flag.clone(),
+    });
+
+    userdata!(Player {
+        "steam_id": |x| x.steam_id.clone(),
+        "name": |x| x.name.clone(),
+        "observer_slot": |x| x.observer_slot,
+        "activity": |x| name(&x.activity),
+        "match_stats": |x| x.match_stats.clone(),
+        "state": |x| x.state.clone(),
+        "team": |x| x.team.as_ref().map(name),
+        "weapons": |x| x.weapons.clone(),
+        "clan": |x| x.clan.clone(),
+    });
+
+    userdata!(player::MatchStats {
+        "kills": |x| x.kills,
+        "assists": |x| x.assists,
+        "deaths": |x| x.deaths,
+        "mvps": |x| x.mvps,
+        "score": |x| x.score,
+    });
+
+    userdata!(player::State {
+        "health": |x| x.health,
+        "armor": |x| x.armor,
+        "helmet": |x| x.helmet,
+        "flashed": |x| x.flashed,
+        "smoked": |x| x.smoked,
+        "burning": |x| x.burning,
+        "money": |x| x.money,
+        "round_kills": |x| x.round_kills,
+        "round_killhs": |x| x.round_killhs,
+        "equip_value": |x| x.equip_value,
+        "round_totaldmg": |x| x.round_totaldmg,
+        "defuse_kit": |x| x.defuse_kit,
+    });
+
+    userdata!(player::Weapon {
+        "name": |x| x.name.clone(),
+        "paintkit": |x| x.paintkit.clone(),
+        "type": |x| x.r#type.as_ref().map(name),
+        "state": |x| name(&x.state),
+        "ammo_clip": |x| x.ammo_clip,
+        "ammo_clip_max": |x| x.ammo_clip_max,
+        "ammo_reserve": |x| x.ammo_reserve,
+    });
+
+    userdata!(Round {
+        "phase": |x| name(&x.phase),
+        "bomb": |x| x.bomb.as_ref().map(name),
+        "win_team": |x| x.win_team.as_ref().map(name),
+    });
+
+    /// register the same helper functions that [`CSGOPackage`] has as globals
+    ///
+    /// updates and their parts can be passed to Lua as userdata, with the same getters as in Rhai,
+    /// whether or not this has been called. enums are passed as their names, like `"CT"`.
+    pub fn load_lua_package(lua: &Lua) -> LuaResult<()> {
+        let globals = lua.globals();
+        globals.set("is_alive", lua.create_function(|_, x: AnyUserData| {
+            match x.borrow::<Player>() {
+                Ok(player) => Ok(player.state.as_ref().is_some_and(|state| state.health > 0)),
+                Err(_) => Ok(x.borrow::<player::State>()?.health > 0),
+            }
+        })?)?;
+        globals.set("active_weapon", lua.create_function(|_, x: AnyUserData| {
+            let player = x.borrow::<Player>()?;
+            let active = player.weapons.values()
+                .find(|weapon| matches!(weapon.state, player::WeaponState::Active | player::WeaponState::Reloading));
+            Ok(active.cloned())
+        })?)?;
+        globals.set("has_weapon_type", lua.create_function(|_, (x, type_name): (AnyUserData, String)| {
+            let player = x.borrow::<Player>()?;
+            Ok(player.weapons.values().any(|weapon| {
+                weapon.r#type.as_ref().is_some_and(|r#type| name(r#type).eq_ignore_ascii_case(&type_name))
+            }))
+        })?)?;
+        globals.set("team_of", lua.create_function(|_, x: AnyUserData| {
+            let team = match x.borrow::<Update>() {
+                Ok(update) => update.player.as_ref().and_then(|player| player.team),
+                Err(_) => x.borrow::<Player>()?.team,
+            };
+            Ok(team.as_ref().map(name))
+        })?)?;
+        globals.set("parsed_round_wins", lua.create_function(|lua, x: AnyUserData| {
+            let map = x.borrow::<Map>()?;
+            let wins = lua.create_table()?;
+            for (round, team, reason) in map.parsed_round_wins() {
+                let win = lua.create_table()?;
+                win.set("round", round)?;
+                win.set("team", name(&team))?;
+                win.set("reason", reason)?;
+                wins.push(win)?;
+            }
+            Ok(wins)
+        })?)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "lua")]
+pub use lua_package::load_lua_package;
+#[cfg(feature = "lua")]
+pub(crate) use lua_package::name as lua_name;
@@ -15,7 +15,7 @@ pub struct Round {
 }
 
 /// round phase
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Phase {
     /// in progress
@@ -27,7 +27,7 @@ pub enum Phase {
 }
 
 /// bomb state
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BombState {
     /// planted
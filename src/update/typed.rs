@@ -0,0 +1,288 @@
+//! updates whose shape follows from their subscriptions, checked at compile time
+//!
+//! a config built with `GSIConfigBuilder::<(MapSub, PlayerStateSub)>::typed(...)` subscribes to
+//! those sets of info, and listeners added with
+//! [`add_typed_listener`](../../struct.GSIServer.html#method.add_typed_listener) get a
+//! `TypedUpdate<(MapSub, PlayerStateSub)>`, where `map` is a [`Map`](../map/struct.Map.html),
+//! `player.state` is a [`State`](../player/struct.State.html), and the sections that weren't
+//! subscribed to are [`Absent`](struct.Absent.html). an update that's missing a subscribed
+//! section doesn't parse.
+//!
+//! ```
+//! use csgo_gsi::GSIConfigBuilder;
+//! use csgo_gsi::update::typed::{MapSub, PlayerStateSub, TypedUpdate};
+//!
+//! type Subs = (MapSub, PlayerStateSub);
+//! let config = GSIConfigBuilder::<Subs>::typed("csgo-gsi Example")
+//!     .try_build()
+//!     .expect("config wasn't valid");
+//! # let raw = r#"{
+//! #     "auth": {},
+//! #     "map": {"mode": "competitive", "name": "de_dust2", "phase": "live", "round": 3,
+//! #         "team_ct": {"score": 2, "consecutive_round_losses": 0, "timeouts_remaining": 1, "matches_won_this_series": 0},
+//! #         "team_t": {"score": 1, "consecutive_round_losses": 1, "timeouts_remaining": 1, "matches_won_this_series": 0},
+//! #         "num_matches_to_win_series": 0, "current_spectators": 0, "souvenirs_total": 0},
+//! #     "player": {"steamid": "76561197960265728", "name": "player", "activity": "playing",
+//! #         "state": {"health": 100, "armor": 0, "helmet": false, "flashed": 0, "smoked": 0, "burning": 0,
+//! #             "money": 800, "round_kills": 0, "round_killhs": 0, "equip_value": 200}}
+//! # }"#;
+//! let update = TypedUpdate::<Subs>::from_json(raw).expect("update should parse");
+//! println!("round {}, {} health", update.map.round, update.player.state.health);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use fehler::throws;
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+use serde::de::{DeserializeOwned, IgnoredAny, Visitor};
+
+use crate::{Error, Subscription};
+use super::{Team, Provider, map::Map, player, round::Round};
+
+/// anything that can be a section of an update
+pub trait SectionData: Clone + fmt::Debug + Serialize + DeserializeOwned {}
+
+impl<T: Clone + fmt::Debug + Serialize + DeserializeOwned> SectionData for T {}
+
+/// whether a section was subscribed to, as a type
+pub trait Flag: Clone + fmt::Debug {
+    /// `T` if the section was subscribed to, `Absent` if not
+    type Section<T: SectionData>: SectionData;
+    /// subscribed if either this or `B` is
+    type Or<B: Flag>: Flag;
+}
+
+/// a section that was subscribed to
+#[derive(Clone, Copy, Debug)]
+pub struct Subscribed;
+
+/// a section that wasn't subscribed to
+#[derive(Clone, Copy, Debug)]
+pub struct Unsubscribed;
+
+impl Flag for Subscribed {
+    type Section<T: SectionData> = T;
+    type Or<B: Flag> = Subscribed;
+}
+
+impl Flag for Unsubscribed {
+    type Section<T: SectionData> = Absent;
+    type Or<B: Flag> = B;
+}
+
+/// `T` if `F` is subscribed, `Absent` if not
+pub type Section<F, T> = <F as Flag>::Section<T>;
+
+/// a section that wasn't subscribed to, and so isn't expected in the update
+///
+/// if CS:GO sends it anyway, it's ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Absent;
+
+impl Serialize for Absent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_none()
+    }
+}
+
+impl<'de> Deserialize<'de> for Absent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AbsentVisitor;
+
+        impl<'de> Visitor<'de> for AbsentVisitor {
+            type Value = Absent;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "anything")
+            }
+
+            fn visit_none<E>(self) -> Result<Absent, E> {
+                Ok(Absent)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Absent, D::Error> {
+                IgnoredAny::deserialize(deserializer)?;
+                Ok(Absent)
+            }
+        }
+
+        // a missing field deserializes as none, so this also works when the section isn't sent
+        deserializer.deserialize_option(AbsentVisitor)
+    }
+}
+
+/// a set of subscriptions, as a type: one of the `...Sub` types, a tuple of them, or `()` for none
+pub trait SubscriptionSet: Clone + fmt::Debug + 'static {
+    /// whether `map` is there
+    type Map: Flag;
+    /// whether `player` is there
+    type Player: Flag;
+    /// whether `player.state` is there
+    type PlayerState: Flag;
+    /// whether `player.match_stats` is there
+    type PlayerMatchStats: Flag;
+    /// whether `provider` is there
+    type Provider: Flag;
+    /// whether `round` is there
+    type Round: Flag;
+
+    /// the subscriptions to put in the config
+    fn subscriptions() -> Vec<Subscription>;
+}
+
+impl SubscriptionSet for () {
+    type Map = Unsubscribed;
+    type Player = Unsubscribed;
+    type PlayerState = Unsubscribed;
+    type PlayerMatchStats = Unsubscribed;
+    type Provider = Unsubscribed;
+    type Round = Unsubscribed;
+
+    fn subscriptions() -> Vec<Subscription> {
+        vec![]
+    }
+}
+
+type Y = Subscribed;
+type N = Unsubscribed;
+
+macro_rules! subscription_types {
+    ($($(#[$attr:meta])* $name:ident = $subscription:ident [$map:ident, $player:ident, $player_state:ident, $player_match_stats:ident, $provider:ident, $round:ident];)*) => {$(
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        pub struct $name;
+
+        impl SubscriptionSet for $name {
+            type Map = $map;
+            type Player = $player;
+            type PlayerState = $player_state;
+            type PlayerMatchStats = $player_match_stats;
+            type Provider = $provider;
+            type Round = $round;
+
+            fn subscriptions() -> Vec<Subscription> {
+                vec![Subscription::$subscription]
+            }
+        }
+    )*};
+}
+
+// the flags are, in order: map, player, player.state, player.match_stats, provider, round
+subscription_types! {
+    /// [`Subscription::MapRoundWins`](../../enum.Subscription.html#variant.MapRoundWins), which fills in `map.round_wins`
+    MapRoundWinsSub = MapRoundWins [N, N, N, N, N, N];
+    /// [`Subscription::Map`](../../enum.Subscription.html#variant.Map), which adds `map`
+    MapSub = Map [Y, N, N, N, N, N];
+    /// [`Subscription::PlayerID`](../../enum.Subscription.html#variant.PlayerID), which adds `player`
+    PlayerIDSub = PlayerID [N, Y, N, N, N, N];
+    /// [`Subscription::PlayerMatchStats`](../../enum.Subscription.html#variant.PlayerMatchStats), which adds `player.match_stats`
+    PlayerMatchStatsSub = PlayerMatchStats [N, Y, N, Y, N, N];
+    /// [`Subscription::PlayerState`](../../enum.Subscription.html#variant.PlayerState), which adds `player.state`
+    PlayerStateSub = PlayerState [N, Y, Y, N, N, N];
+    /// [`Subscription::PlayerWeapons`](../../enum.Subscription.html#variant.PlayerWeapons), which fills in `player.weapons`
+    PlayerWeaponsSub = PlayerWeapons [N, Y, N, N, N, N];
+    /// [`Subscription::Provider`](../../enum.Subscription.html#variant.Provider), which adds `provider`
+    ProviderSub = Provider [N, N, N, N, Y, N];
+    /// [`Subscription::Round`](../../enum.Subscription.html#variant.Round), which adds `round`
+    RoundSub = Round [N, N, N, N, N, Y];
+}
+
+macro_rules! tuple_subscription_sets {
+    ($head:ident) => {
+        impl<$head: SubscriptionSet> SubscriptionSet for ($head,) {
+            type Map = $head::Map;
+            type Player = $head::Player;
+            type PlayerState = $head::PlayerState;
+            type PlayerMatchStats = $head::PlayerMatchStats;
+            type Provider = $head::Provider;
+            type Round = $head::Round;
+
+            fn subscriptions() -> Vec<Subscription> {
+                $head::subscriptions()
+            }
+        }
+    };
+    ($head:ident, $($tail:ident),+) => {
+        impl<$head: SubscriptionSet, $($tail: SubscriptionSet),+> SubscriptionSet for ($head, $($tail),+) {
+            type Map = <$head::Map as Flag>::Or<<($($tail,)+) as SubscriptionSet>::Map>;
+            type Player = <$head::Player as Flag>::Or<<($($tail,)+) as SubscriptionSet>::Player>;
+            type PlayerState = <$head::PlayerState as Flag>::Or<<($($tail,)+) as SubscriptionSet>::PlayerState>;
+            type PlayerMatchStats = <$head::PlayerMatchStats as Flag>::Or<<($($tail,)+) as SubscriptionSet>::PlayerMatchStats>;
+            type Provider = <$head::Provider as Flag>::Or<<($($tail,)+) as SubscriptionSet>::Provider>;
+            type Round = <$head::Round as Flag>::Or<<($($tail,)+) as SubscriptionSet>::Round>;
+
+            fn subscriptions() -> Vec<Subscription> {
+                let mut subscriptions = $head::subscriptions();
+                subscriptions.extend(<($($tail,)+) as SubscriptionSet>::subscriptions());
+                subscriptions
+            }
+        }
+
+        tuple_subscription_sets!($($tail),+);
+    };
+}
+
+tuple_subscription_sets!(A, B, C, D, E, F, G, H);
+
+/// an update received from CS:GO, with exactly the sections subscribed to in `S`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound = "", deny_unknown_fields)]
+pub struct TypedUpdate<S: SubscriptionSet> {
+    /// map info
+    pub map: Section<S::Map, Map>,
+    /// player info
+    pub player: Section<S::Player, TypedPlayer<S>>,
+    /// provider (CS:GO) info
+    pub provider: Section<S::Provider, Provider>,
+    /// authentication info, matching initial config
+    pub auth: HashMap<String, String>,
+    /// round info
+    pub round: Section<S::Round, Round>,
+    #[allow(dead_code)]
+    #[serde(skip_serializing, default)]
+    added: IgnoredAny,
+    #[allow(dead_code)]
+    #[serde(skip_serializing, default)]
+    previously: IgnoredAny,
+}
+
+impl<S: SubscriptionSet> TypedUpdate<S> {
+    /// parse an update from a raw request body, reporting exactly where it went wrong if it doesn't parse
+    #[throws]
+    pub fn from_json(raw: &str) -> Self {
+        super::parse(raw)?
+    }
+
+    /// parse an update from a raw request body that's already been parsed as JSON
+    #[throws]
+    pub(crate) fn from_json_value(json_value: &serde_json::Value, raw: &str) -> Self {
+        super::parse_value(json_value, raw)?
+    }
+}
+
+/// player info, with exactly the parts subscribed to in `S`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound = "", deny_unknown_fields)]
+pub struct TypedPlayer<S: SubscriptionSet> {
+    /// steam ID
+    #[serde(rename = "steamid")]
+    pub steam_id: String,
+    /// display name
+    pub name: String,
+    /// observer slot number
+    pub observer_slot: Option<u64>,
+    /// current activity (in menu, playing game, etc)
+    pub activity: player::Activity,
+    /// match statistics
+    pub match_stats: Section<S::PlayerMatchStats, player::MatchStats>,
+    /// state (health, armor, etc)
+    pub state: Section<S::PlayerState, player::State>,
+    /// team
+    pub team: Option<Team>,
+    /// weapon inventory
+    #[serde(default)]
+    pub weapons: HashMap<String, player::Weapon>,
+    /// clan
+    pub clan: Option<String>,
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+
+use csgo_gsi::capture::{CapturedUpdate, Recorder, Rotation};
+use flate2::read::GzDecoder;
+
+mod common;
+use common::{UPDATE, captured};
+
+#[test]
+fn test_record_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.ndjson");
+    let mut recorder = Recorder::new(&path);
+    for _ in 0..3 {
+        recorder.record(&captured()).unwrap();
+    }
+    drop(recorder);
+
+    let lines = fs::read_to_string(&path).unwrap();
+    let records = lines.lines()
+        .map(|line| serde_json::from_str::<CapturedUpdate>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].raw, UPDATE);
+    assert_eq!(records[0].update.provider.as_ref().unwrap().app_id, 730);
+}
+
+#[test]
+fn test_record_gzip_with_size_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.ndjson.gz");
+    let mut recorder = Recorder::new(&path)
+        .gzip(true)
+        .rotation(Rotation::Size(1));
+    for _ in 0..2 {
+        recorder.record(&captured()).unwrap();
+    }
+    recorder.close().unwrap();
+
+    for name in &["capture.ndjson.gz", "capture.1.ndjson.gz"] {
+        let file = fs::File::open(dir.path().join(name)).unwrap();
+        let lines = BufReader::new(GzDecoder::new(file)).lines().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(lines.len(), 1);
+        serde_json::from_str::<CapturedUpdate>(&lines[0]).unwrap();
+    }
+    assert!(!dir.path().join("capture.2.ndjson.gz").exists());
+}
+
+#[test]
+fn test_captures_can_be_read_while_recording() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.ndjson");
+    let gzip_path = dir.path().join("capture.ndjson.gz");
+    let mut recorder = Recorder::new(&path);
+    let mut gzip_recorder = Recorder::new(&gzip_path).gzip(true);
+    for _ in 0..2 {
+        recorder.record(&captured()).unwrap();
+        gzip_recorder.record(&captured()).unwrap();
+    }
+
+    let lines = fs::read_to_string(&path).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+    // the gzip stream isn't finished, but everything recorded so far can be decoded
+    let file = fs::File::open(&gzip_path).unwrap();
+    let lines = BufReader::new(GzDecoder::new(file)).lines()
+        .map_while(Result::ok)
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    for line in &lines {
+        serde_json::from_str::<CapturedUpdate>(line).unwrap();
+    }
+}
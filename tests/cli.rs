@@ -0,0 +1,123 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use csgo_gsi::capture::{CapturedUpdate, Recorder};
+use csgo_gsi::replay::Replayer;
+use flate2::read::GzDecoder;
+
+mod common;
+use common::captured;
+
+/// run the `csgo-gsi` binary with the given arguments
+fn csgo_gsi<I: IntoIterator<Item=S>, S: AsRef<OsStr>>(args: I) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_csgo-gsi"))
+        .args(args)
+        .output()
+        .expect("couldn't run csgo-gsi")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_install_and_uninstall() {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let cfg_folder_arg = cfg_folder.path().to_str().unwrap();
+    let cfg_path = cfg_folder.path().join("gamestate_integration_cli test.cfg");
+
+    let output = csgo_gsi(["install", "--name", "cli test", "--port", "31999", "--auth-token", "hunter2", "--cfg-folder", cfg_folder_arg]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let installed = fs::read_to_string(&cfg_path).unwrap();
+    assert!(installed.contains("http://127.0.0.1:31999"), "{}", installed);
+    assert!(installed.contains("hunter2"), "{}", installed);
+
+    fs::write(cfg_folder.path().join("gamestate_integration_other.cfg"), "\"other\" {}").unwrap();
+    let output = csgo_gsi(["list-configs", "--cfg-folder", cfg_folder_arg]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "gamestate_integration_cli test.cfg (managed by csgo-gsi)\ngamestate_integration_other.cfg\n");
+
+    let output = csgo_gsi(["uninstall", "--name", "cli test", "--cfg-folder", cfg_folder_arg]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!cfg_path.exists());
+    assert!(cfg_folder.path().join("gamestate_integration_other.cfg").exists());
+}
+
+fn record(path: &Path, captures: &[CapturedUpdate]) {
+    let mut recorder = Recorder::new(path);
+    for captured in captures {
+        recorder.record(captured).unwrap();
+    }
+    recorder.close().unwrap();
+}
+
+#[test]
+fn test_audit() {
+    let folder = tempfile::tempdir().unwrap();
+    let good = folder.path().join("good.ndjson");
+    record(&good, &[captured(), captured()]);
+    let output = csgo_gsi([OsStr::new("audit"), good.as_os_str()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(stdout(&output), "");
+
+    let bad = folder.path().join("bad.ndjson");
+    let mut broken = captured();
+    broken.raw = broken.raw.replace("\"money\": 3150", "\"money\": -5");
+    assert_ne!(broken.raw, captured().raw);
+    record(&bad, &[captured(), broken]);
+    let output = csgo_gsi([OsStr::new("audit"), bad.as_os_str()]);
+    assert_eq!(output.status.code(), Some(1));
+    let report = stdout(&output);
+    assert!(report.starts_with("line 2: /player/state/money: got -5 ("), "{}", report);
+    assert_eq!(report.lines().count(), 1, "{}", report);
+}
+
+/// wait for something to happen, failing the test if it takes too long
+fn wait_for<T, F: FnMut() -> Option<T>>(what: &str, mut f: F) -> T {
+    let started = Instant::now();
+    loop {
+        if let Some(value) = f() {
+            return value;
+        }
+        assert!(started.elapsed() < Duration::from_secs(10), "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_record_finishes_on_ctrl_c() {
+    let folder = tempfile::tempdir().unwrap();
+    let capture = folder.path().join("capture.ndjson.gz");
+    let mut recording = Command::new(env!("CARGO_BIN_EXE_csgo-gsi"))
+        .arg("record").arg(&capture)
+        .args(["--gzip", "--name", "recording", "--port", "0", "--cfg-folder"]).arg(folder.path())
+        .spawn()
+        .unwrap();
+
+    // with port 0, the installed config is the only place to find out which port was picked
+    let cfg_path = folder.path().join("gamestate_integration_recording.cfg");
+    let uri = wait_for("the config to be installed", || {
+        let installed = fs::read_to_string(&cfg_path).ok()?;
+        let uri = installed.lines().find_map(|line| line.trim().strip_prefix("\"uri\"\t\""))?;
+        Some(uri.strip_suffix('"')?.to_string())
+    });
+    Replayer::from_captures(vec![captured()]).replay_to(&uri).await.unwrap();
+    wait_for("the update to be recorded", || fs::metadata(&capture).ok().filter(|metadata| metadata.len() > 0));
+
+    let interrupted = Command::new("kill").arg("-INT").arg(recording.id().to_string()).status().unwrap();
+    assert!(interrupted.success());
+    let status = wait_for("csgo-gsi to exit", || recording.try_wait().unwrap());
+    assert!(status.success(), "{:?}", status);
+
+    // a gzip stream that wasn't finished fails with an unexpected end of file
+    let mut recorded = String::new();
+    GzDecoder::new(fs::File::open(&capture).unwrap()).read_to_string(&mut recorded).unwrap();
+    assert_eq!(recorded.lines().count(), 1);
+    serde_json::from_str::<CapturedUpdate>(recorded.trim_end()).unwrap();
+}
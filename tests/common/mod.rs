@@ -0,0 +1,87 @@
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::net::SocketAddr;
+use std::path::Path;
+#[cfg(any(feature = "lua", feature = "rhai"))]
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use csgo_gsi::{GSIServer, Update};
+use csgo_gsi::capture::CapturedUpdate;
+use csgo_gsi::update::typed::SubscriptionSet;
+
+pub const UPDATE: &str = include_str!("../fixtures/update.json");
+
+#[allow(dead_code)]
+pub fn captured() -> CapturedUpdate {
+    let update: Update = serde_json::from_str(UPDATE).expect("fixture should parse");
+    CapturedUpdate::now(UPDATE, update)
+}
+
+/// run a server on an ephemeral port in the background, installed into a temporary folder
+#[allow(dead_code)]
+pub fn spawn_server<S: SubscriptionSet, F: 'static + Send + FnOnce() -> GSIServer<S>>(make_server: F) -> SocketAddr {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let cfg_folder = tempfile::tempdir().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let mut server = make_server();
+            let addr = server.bind().await.unwrap();
+            server.install_into(cfg_folder.path()).unwrap();
+            tx.send(addr).unwrap();
+            server.run().await.unwrap();
+        });
+    });
+    rx.recv().expect("server failed to start")
+}
+
+/// GET the given URI, returning the response and its body as a string
+#[allow(dead_code)]
+pub async fn get(uri: &str) -> (gotham::hyper::Response<()>, String) {
+    let client = gotham::hyper::Client::new();
+    let response = client.get(uri.parse().unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = gotham::hyper::body::to_bytes(body).await.unwrap();
+    (gotham::hyper::Response::from_parts(parts, ()), String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// write a script or plugin, making sure its modification time changes even on coarse filesystems
+#[allow(dead_code)]
+pub fn write_script(path: &Path, script: &str, age: u64) {
+    fs::write(path, script).unwrap();
+    let modified = SystemTime::now() - Duration::from_secs(age);
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+/// a Rhai script host whose scripts can `record` strings into the returned list
+#[cfg(feature = "rhai")]
+#[allow(dead_code)]
+pub fn rhai_host_recording() -> (csgo_gsi::scripting::ScriptHost, Rc<RefCell<Vec<String>>>) {
+    use rhai::{ImmutableString, RegisterFn};
+
+    let records = Rc::new(RefCell::new(vec![]));
+    let mut host = csgo_gsi::scripting::ScriptHost::new();
+    let recorder = records.clone();
+    host.engine_mut().register_fn("record", move |record: ImmutableString| recorder.borrow_mut().push(record.to_string()));
+    (host, records)
+}
+
+/// a Lua script host whose scripts can `record` strings into the returned list
+#[cfg(feature = "lua")]
+#[allow(dead_code)]
+pub fn lua_host_recording() -> (csgo_gsi::scripting::LuaScriptHost, Rc<RefCell<Vec<String>>>) {
+    let records = Rc::new(RefCell::new(vec![]));
+    let mut host = csgo_gsi::scripting::LuaScriptHost::new();
+    let recorder = records.clone();
+    host.on_setup(move |lua| {
+        let recorder = recorder.clone();
+        lua.globals().set("record", lua.create_function(move |_, record: String| {
+            recorder.borrow_mut().push(record);
+            Ok(())
+        })?)
+    });
+    (host, records)
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use csgo_gsi::{Error, GSIConfig, GSIConfigBuilder, GSIServer, Subscription};
+
+#[derive(Deserialize)]
+struct Precision {
+    precision_time: u8,
+    precision_position: u8,
+    precision_vector: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Managed by the csgo-gsi Rust library")]
+struct ConfigFile {
+    uri: String,
+    timeout: f64,
+    buffer: f64,
+    throttle: f64,
+    heartbeat: f64,
+    auth: HashMap<String, String>,
+    output: Precision,
+    data: HashMap<String, bool>,
+}
+
+const DATA_KEYS: &[(Subscription, &str)] = &[
+    (Subscription::MapRoundWins, "map_round_wins"),
+    (Subscription::Map, "map"),
+    (Subscription::PlayerID, "player_id"),
+    (Subscription::PlayerMatchStats, "player_match_stats"),
+    (Subscription::PlayerState, "player_state"),
+    (Subscription::PlayerWeapons, "player_weapons"),
+    (Subscription::Provider, "provider"),
+    (Subscription::Round, "round"),
+    (Subscription::AllGrenades, "allgrenades"),
+    (Subscription::AllPlayersID, "allplayers_id"),
+    (Subscription::AllPlayersMatchStats, "allplayers_match_stats"),
+    (Subscription::AllPlayersPosition, "allplayers_position"),
+    (Subscription::AllPlayersState, "allplayers_state"),
+    (Subscription::AllPlayersWeapons, "allplayers_weapons"),
+    (Subscription::Bomb, "bomb"),
+    (Subscription::PhaseCountdowns, "phase_countdowns"),
+    (Subscription::PlayerPosition, "player_position"),
+];
+
+fn installed(config: GSIConfig) -> ConfigFile {
+    installed_by(GSIServer::new(config, 31344))
+}
+
+fn installed_by(mut server: GSIServer) -> ConfigFile {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let cfg_path = cfg_folder.path().join(format!("gamestate_integration_{}.cfg", server.config().service_name()));
+    server.install_into(cfg_folder.path()).unwrap();
+    let installed = fs::read_to_string(cfg_path).unwrap();
+    vdf_serde::from_str(&installed).unwrap_or_else(|err| panic!("{}: {}", err, installed))
+}
+
+fn invalid(builder: &GSIConfigBuilder) -> &'static str {
+    match builder.try_build() {
+        Err(Error::InvalidConfig { description }) => description,
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("config should have been invalid"),
+    }
+}
+
+#[test]
+fn test_defaults_round_trip() {
+    let config = installed(GSIConfigBuilder::new("defaults").try_build().unwrap());
+    assert_eq!(config.uri, "http://127.0.0.1:31344");
+    assert_eq!(config.timeout, 1.1);
+    assert_eq!(config.buffer, 0.1);
+    assert_eq!(config.throttle, 1.0);
+    assert_eq!(config.heartbeat, 60.0);
+    assert!(config.auth.is_empty());
+    assert_eq!(config.output.precision_time, 2);
+    assert_eq!(config.output.precision_position, 2);
+    assert_eq!(config.output.precision_vector, 2);
+    assert_eq!(config.data.len(), DATA_KEYS.len());
+    assert!(config.data.values().all(|subscribed| !subscribed));
+}
+
+#[test]
+fn test_every_field_round_trips() {
+    let config = GSIConfigBuilder::new("every-field")
+        .timeout(Duration::from_millis(2500))
+        .buffer(Duration::from_millis(250))
+        .throttle(Duration::from_millis(500))
+        .heartbeat(Duration::from_secs(30))
+        .auth("token", "hunter2")
+        .auth("other", "value")
+        .precision_time(1)
+        .precision_position(3)
+        .precision_vector(6)
+        .subscribe_multiple(Subscription::UNRESTRICTED)
+        .subscribe_multiple(Subscription::SPECTATOR_ONLY)
+        .try_build()
+        .unwrap();
+    assert_eq!(config.heartbeat(), Duration::from_secs(30));
+    let config = installed(config);
+    assert_eq!(config.timeout, 2.5);
+    assert_eq!(config.buffer, 0.25);
+    assert_eq!(config.throttle, 0.5);
+    assert_eq!(config.heartbeat, 30.0);
+    assert_eq!(config.auth.len(), 2);
+    assert_eq!(config.auth["token"], "hunter2");
+    assert_eq!(config.auth["other"], "value");
+    assert_eq!(config.output.precision_time, 1);
+    assert_eq!(config.output.precision_position, 3);
+    assert_eq!(config.output.precision_vector, 6);
+    assert!(config.data.values().all(|subscribed| *subscribed));
+}
+
+#[test]
+fn test_each_subscription_round_trips() {
+    for (subscription, key) in DATA_KEYS {
+        let config = installed(GSIConfigBuilder::new("subscription").subscribe(*subscription).try_build().unwrap());
+        for (other, subscribed) in &config.data {
+            assert_eq!(*subscribed, other == key, "subscribing to {:?} set {}", subscription, other);
+        }
+    }
+}
+
+#[test]
+fn test_invalid_values_are_rejected() {
+    let zero = Duration::from_secs(0);
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").timeout(zero)), "timeout must not be zero");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").buffer(zero)), "buffer must not be zero");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").throttle(zero)), "throttle must not be zero");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").heartbeat(zero)), "heartbeat must not be zero");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").heartbeat(Duration::from_millis(500))), "throttle must not be longer than heartbeat");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").precision_time(7)), "time precision must be at most 6 digits");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").precision_position(7)), "position precision must be at most 6 digits");
+    assert_eq!(invalid(GSIConfigBuilder::new("invalid").precision_vector(7)), "vector precision must be at most 6 digits");
+}
+
+#[test]
+fn test_listener_requirements_are_subscribed() {
+    let config = GSIConfigBuilder::new("required")
+        .subscribe(Subscription::Provider)
+        .try_build()
+        .unwrap();
+    let mut server = GSIServer::new(config, 31344);
+    server.add_listener_requiring([Subscription::Map, Subscription::PlayerState], |_| {});
+    server.add_listener_requiring(vec![Subscription::Map, Subscription::Round], |_| {});
+    let config = installed_by(server);
+    for (subscription, key) in DATA_KEYS {
+        let required = [Subscription::Provider, Subscription::Map, Subscription::PlayerState, Subscription::Round].contains(subscription);
+        assert_eq!(config.data[*key], required, "{:?}", subscription);
+    }
+}
+
+#[test]
+fn test_services_are_installed_with_their_own_uri() {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let config = GSIConfigBuilder::new("main").try_build().unwrap();
+    let mut server = GSIServer::new(config, 31344);
+    let config = GSIConfigBuilder::new("coach")
+        .auth("token", "hunter2")
+        .try_build()
+        .unwrap();
+    server.add_service(config).unwrap()
+        .add_listener_requiring(vec![Subscription::PlayerState], |_| {});
+    server.install_into(cfg_folder.path()).unwrap();
+
+    let installed = fs::read_to_string(cfg_folder.path().join("gamestate_integration_coach.cfg")).unwrap();
+    let service: ConfigFile = vdf_serde::from_str(&installed).unwrap_or_else(|err| panic!("{}: {}", err, installed));
+    assert_eq!(service.uri, "http://127.0.0.1:31344/svc/coach");
+    assert_eq!(service.auth["token"], "hunter2");
+    assert!(service.data["player_state"]);
+    assert!(cfg_folder.path().join("gamestate_integration_main.cfg").exists());
+
+    server.uninstall_from(cfg_folder.path()).unwrap();
+    assert!(!cfg_folder.path().join("gamestate_integration_coach.cfg").exists());
+}
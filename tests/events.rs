@@ -0,0 +1,32 @@
+use csgo_gsi::{GSIConfigBuilder, Subscription};
+use csgo_gsi::events::{Event, EventDetector};
+use csgo_gsi::simulator::{MatchScript, RoundEnd, Simulator};
+use csgo_gsi::update::{Team, map};
+
+#[test]
+fn test_events_from_simulated_match() {
+    let config = GSIConfigBuilder::new("events")
+        .subscribe_multiple(Subscription::UNRESTRICTED)
+        .try_build().unwrap();
+    let script = MatchScript::new("de_inferno")
+        .round(Team::CT, RoundEnd::Elimination)
+        .round(Team::T, RoundEnd::BombExploded);
+    let captures = Simulator::new(&config, script).unwrap().captures().unwrap();
+
+    let mut detector = EventDetector::new();
+    let events = captures.iter()
+        .flat_map(|captured| detector.detect(&captured.update))
+        .collect::<Vec<_>>();
+    assert_eq!(events, vec![
+        Event::MapChanged { name: "de_inferno".to_string() },
+        Event::MapPhaseChanged { phase: map::Phase::Live },
+        Event::RoundStarted { round: 1 },
+        Event::RoundEnded { round: 1, winner: Some(Team::CT) },
+        Event::RoundStarted { round: 2 },
+        Event::BombPlanted,
+        Event::BombExploded,
+        Event::PlayerDied { steam_id: "76561197960265728".to_string() },
+        Event::RoundEnded { round: 2, winner: Some(Team::T) },
+    ]);
+    assert_eq!(serde_json::to_value(&events[3]).unwrap(), serde_json::json!({"type": "round_ended", "round": 1, "winner": "CT"}));
+}
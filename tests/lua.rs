@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use csgo_gsi::Error;
+use csgo_gsi::events::Event;
+use csgo_gsi::scripting::{LuaLimits, LuaScriptHost};
+use csgo_gsi::update::Team;
+
+mod common;
+use common::{lua_host_recording, write_script};
+
+const COUNTER: &str = r#"
+local count = 0
+
+function on_update(update)
+    count = count + 1
+    record("update " .. count .. " on round " .. update.map.round)
+end
+
+function on_round_end(round, winner)
+    record("round " .. round .. " won by " .. winner)
+end
+"#;
+
+#[test]
+fn test_hooks_keep_state() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("counter.lua");
+    write_script(&path, COUNTER, 60);
+    let (mut host, records) = lua_host_recording();
+    host.load(&path).unwrap();
+
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+    assert!(host.handle_update(&update).is_empty());
+    assert!(host.handle_event(&Event::RoundEnded { round: 3, winner: Some(Team::CT) }).is_empty());
+    assert!(host.handle_event(&Event::BombPlanted).is_empty());
+    assert_eq!(*records.borrow(), vec!["update 1 on round 3", "update 2 on round 3", "round 3 won by CT"]);
+
+    // a broken version is reported, and the old one keeps running
+    write_script(&path, "function on_update(update)", 0);
+    match host.handle_update(&update).as_slice() {
+        [Error::ScriptError { hook: None, .. }] => {}
+        errors => panic!("expected a load error, got {:?}", errors),
+    }
+    assert_eq!(records.borrow().last().unwrap(), "update 3 on round 3");
+}
+
+const HELPERS: &str = r#"
+function on_update(update, previous)
+    local player = update.player
+    record("alive " .. tostring(is_alive(player)) .. ", team " .. team_of(update) .. ", holding " .. active_weapon(player).name)
+    record("rifle " .. tostring(has_weapon_type(player, "rifle")) .. ", sniper " .. tostring(has_weapon_type(player, "SniperRifle")))
+    for _, win in ipairs(parsed_round_wins(update.map)) do
+        record("round " .. win.round .. ": " .. win.team .. " by " .. win.reason)
+    end
+    if previous == nil then
+        record("first update")
+    elseif previous.player.state.money ~= player.state.money then
+        record("money changed")
+    end
+    state.updates = (state.updates or 0) + 1
+end
+
+function on_event(event)
+    record(event.type .. " " .. event.round .. " " .. event.winner)
+end
+"#;
+
+#[test]
+fn test_helpers() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("helpers.lua");
+    write_script(&path, HELPERS, 60);
+    let (mut host, records) = lua_host_recording();
+    host.load(&path).unwrap();
+
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+    let mut richer = update.clone();
+    richer.player.as_mut().unwrap().state.as_mut().unwrap().money += 300;
+    assert!(host.handle_update(&richer).is_empty());
+    assert!(host.handle_event(&Event::RoundEnded { round: 3, winner: Some(Team::CT) }).is_empty());
+    assert_eq!(*records.borrow(), vec![
+        "alive true, team CT, holding weapon_m4a1_silencer",
+        "rifle true, sniper false",
+        "round 1: T by elimination",
+        "round 2: CT by defuse",
+        "round 3: CT by time",
+        "first update",
+        "alive true, team CT, holding weapon_m4a1_silencer",
+        "rifle true, sniper false",
+        "round 1: T by elimination",
+        "round 2: CT by defuse",
+        "round 3: CT by time",
+        "money changed",
+        "round_ended 3 CT",
+    ]);
+
+    // `state` outlives reloads
+    write_script(&path, "function on_update(update) record(\"updates so far: \" .. state.updates) end", 0);
+    assert!(host.handle_update(&update).is_empty());
+    assert_eq!(records.borrow().last().unwrap(), "updates so far: 2");
+}
+
+#[test]
+fn test_state_that_isnt_json_is_reset() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("callback.lua");
+    write_script(&path, "state.updates = 1 state.callback = function() end", 60);
+    let (mut host, records) = lua_host_recording();
+    host.load(&path).unwrap();
+
+    write_script(&path, "function on_update(update) record(tostring(state.updates)) end", 0);
+    match host.handle_update(&common::captured().update).as_slice() {
+        [Error::ScriptError { hook: None, message, .. }] => assert!(message.starts_with("state couldn't be kept, so it was reset"), "{}", message),
+        errors => panic!("expected a reset error, got {:?}", errors),
+    }
+    assert_eq!(*records.borrow(), vec!["nil"]);
+}
+
+#[test]
+fn test_scripts_are_sandboxed() {
+    let folder = tempfile::tempdir().unwrap();
+    let update = common::captured().update;
+    let mut host = LuaScriptHost::with_limits(LuaLimits {
+        time_budget: Duration::from_millis(50),
+        ..LuaLimits::default()
+    });
+    let spinning = folder.path().join("spinning.lua");
+    write_script(&spinning, "function on_update(update) while true do end end", 0);
+    host.load(&spinning).unwrap();
+    let growing = folder.path().join("growing.lua");
+    write_script(&growing, "function on_update(update) local s = 'x' while true do s = s .. s end end", 0);
+    host.load(&growing).unwrap();
+    // catching the error doesn't buy any more time
+    let catching = folder.path().join("catching.lua");
+    write_script(&catching, "function on_update(update) for i = 1, 50 do pcall(function() while true do end end) end end", 0);
+    host.load(&catching).unwrap();
+    let catching_forever = folder.path().join("catching_forever.lua");
+    write_script(&catching_forever, "local function spin() while true do end end function on_update(update) while true do pcall(spin) end end", 0);
+    host.load(&catching_forever).unwrap();
+
+    let started = std::time::Instant::now();
+    let errors = host.handle_update(&update);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    for error in &errors {
+        match error {
+            Error::ScriptError { hook: Some("on_update"), message, .. } => assert!(message.contains("disabled"), "{}", message),
+            error => panic!("expected a hook error, got {:?}", error),
+        }
+    }
+    assert_eq!(host.disabled_scripts().count(), 4);
+    assert!(host.handle_update(&update).is_empty());
+
+    let reader = folder.path().join("reader.lua");
+    write_script(&reader, "assert(io == nil and os == nil and dofile == nil and loadfile == nil and require == nil)", 0);
+    host.load(&reader).unwrap();
+    write_script(&reader, "dofile('/etc/passwd')", 0);
+    assert!(LuaScriptHost::new().load(&reader).is_err());
+
+    // bytecode can't be loaded, however it's made
+    let bytecode = folder.path().join("bytecode.lua");
+    write_script(&bytecode, "load(string.dump(function() end))", 0);
+    assert!(LuaScriptHost::new().load(&bytecode).is_err());
+    write_script(&bytecode, r#"
+        local chunk, error = load("\27Lua", "bytecode", "b")
+        assert(chunk == nil and error:find("binary chunk"), error)
+        assert(load("return 1 + 1", "source", "t", {})() == 2)
+    "#, 0);
+    host.load(&bytecode).unwrap();
+}
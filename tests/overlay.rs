@@ -0,0 +1,44 @@
+use std::fs;
+
+use csgo_gsi::{GSIConfigBuilder, GSIServer};
+
+mod common;
+
+#[tokio::test]
+async fn test_overlay_is_served_with_theme() {
+    let theme_folder = tempfile::tempdir().unwrap();
+    let theme = theme_folder.path().join("theme.css");
+    fs::write(&theme, "body { color: hotpink; }").unwrap();
+
+    let default_addr = common::spawn_server(|| {
+        let config = GSIConfigBuilder::new("overlay").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.serve_overlay();
+        server
+    });
+    let themed_theme = theme.clone();
+    let themed_addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("overlay").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.serve_overlay();
+        server.overlay_theme(themed_theme);
+        server
+    });
+
+    let (response, body) = common::get(&format!("http://{}/overlay", default_addr)).await;
+    assert_eq!(response.status(), 200);
+    assert!(body.contains("href=\"/overlay/theme.css\""));
+    assert!(body.contains("fetch(\"/state\")"));
+    assert!(body.contains("new EventSource(\"/events\")"));
+    let (response, body) = common::get(&format!("http://{}/overlay/theme.css", default_addr)).await;
+    assert_eq!(response.status(), 200);
+    assert!(body.contains("#scoreboard"));
+    let (response, _) = common::get(&format!("http://{}/state", default_addr)).await;
+    assert_eq!(response.status(), 404);
+
+    let (_, body) = common::get(&format!("http://{}/overlay/theme.css", themed_addr)).await;
+    assert_eq!(body, "body { color: hotpink; }");
+    fs::write(&theme, "body { color: rebeccapurple; }").unwrap();
+    let (_, body) = common::get(&format!("http://{}/overlay/theme.css", themed_addr)).await;
+    assert_eq!(body, "body { color: rebeccapurple; }");
+}
@@ -0,0 +1,84 @@
+use std::sync::mpsc;
+
+use csgo_gsi::{GSIConfigBuilder, GSIServer, Subscription, Update};
+use csgo_gsi::capture::{CapturedUpdate, Recorder};
+use csgo_gsi::replay::{Replayer, Speed};
+
+mod common;
+use common::UPDATE;
+
+fn captures() -> Vec<CapturedUpdate> {
+    (0..4u64).map(|round| {
+        let mut json: serde_json::Value = serde_json::from_str(UPDATE).unwrap();
+        json["map"]["round"] = round.into();
+        let raw = json.to_string();
+        let update: Update = serde_json::from_value(json).unwrap();
+        let mut captured = CapturedUpdate::now(raw, update);
+        captured.received_at = 1_000 + round * 10;
+        captured
+    }).collect()
+}
+
+#[test]
+fn test_replay_file_with_seek() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.ndjson.gz");
+    let mut recorder = Recorder::new(&path).gzip(true);
+    for captured in captures() {
+        recorder.record(&captured).unwrap();
+    }
+    drop(recorder);
+
+    let replayer = Replayer::open(&path).unwrap()
+        .speed(Speed::Accelerated(10.0))
+        .seek_to_round(2).unwrap();
+    let mut rounds = vec![];
+    replayer.replay_into(|update| rounds.push(update.map.as_ref().unwrap().round));
+    assert_eq!(rounds, vec![2, 3]);
+
+    assert!(Replayer::from_captures(captures()).seek_to_round(7).is_err());
+}
+
+#[tokio::test]
+async fn test_replay_to_server() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("csgo-gsi replay test")
+            .subscribe_multiple(Subscription::UNRESTRICTED)
+            .try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.add_listener(move |update| tx.send(update.map.as_ref().unwrap().round).unwrap());
+        server
+    });
+
+    Replayer::from_captures(captures())
+        .replay_to(&format!("http://{}/", addr))
+        .await
+        .unwrap();
+    let rounds = rx.iter().take(4).collect::<Vec<_>>();
+    assert_eq!(rounds, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_audit_reports_json_pointers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture.ndjson");
+    let mut recorder = Recorder::new(&path);
+    let mut captures = captures();
+    captures[1].raw = captures[1].raw.replace("\"money\":3150", "\"money\":-5");
+    for captured in &captures {
+        recorder.record(captured).unwrap();
+    }
+    drop(recorder);
+
+    let failures = csgo_gsi::replay::audit(&path).unwrap();
+    assert_eq!(failures.len(), 1);
+    match &failures[0] {
+        (2, csgo_gsi::Error::UpdateSchema { path, value, cause, .. }) => {
+            assert_eq!(path, "/player/state/money");
+            assert_eq!(value, &Some(serde_json::json!(-5)));
+            assert!(cause.to_string().contains("expected u64"), "{}", cause);
+        }
+        other => panic!("unexpected audit result {:?}", other),
+    }
+}
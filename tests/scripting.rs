@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use csgo_gsi::Error;
+use csgo_gsi::events::Event;
+use csgo_gsi::scripting::{ScriptHost, ScriptLimits};
+use csgo_gsi::update::Team;
+
+mod common;
+use common::{rhai_host_recording, write_script};
+
+const COUNTER: &str = r#"
+let count = 0;
+
+fn on_update(update) {
+    count += 1;
+    record("update " + count + " on round " + update.map.round);
+}
+
+fn on_round_end(round, winner) {
+    record("round " + round + " won by " + winner);
+}
+"#;
+
+#[test]
+fn test_hooks_keep_state() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("counter.rhai");
+    write_script(&path, COUNTER, 0);
+    let (mut host, records) = rhai_host_recording();
+    host.load(&path).unwrap();
+
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+    assert!(host.handle_update(&update).is_empty());
+    assert!(host.handle_event(&Event::RoundEnded { round: 3, winner: Some(Team::CT) }).is_empty());
+    assert!(host.handle_event(&Event::BombPlanted).is_empty());
+    assert_eq!(*records.borrow(), vec!["update 1 on round 3", "update 2 on round 3", "round 3 won by CT"]);
+}
+
+#[test]
+fn test_scripts_hot_reload() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("reload.rhai");
+    write_script(&path, COUNTER, 60);
+    let (mut host, records) = rhai_host_recording();
+    host.load(&path).unwrap();
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+
+    // a broken version is reported, and the old one keeps running
+    write_script(&path, "fn on_update(update) {", 30);
+    match host.handle_update(&update).as_slice() {
+        [Error::ScriptError { hook: None, .. }] => {}
+        errors => panic!("expected a load error, got {:?}", errors),
+    }
+    assert_eq!(records.borrow().last().unwrap(), "update 2 on round 3");
+
+    // a fixed version starts over
+    write_script(&path, COUNTER, 0);
+    assert!(host.handle_update(&update).is_empty());
+    assert_eq!(records.borrow().last().unwrap(), "update 1 on round 3");
+}
+
+#[test]
+fn test_script_errors_are_reported() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("broken.rhai");
+    write_script(&path, "fn on_bomb_planted() { throw \"oh no\"; }", 0);
+    let mut host = ScriptHost::new();
+    host.load(&path).unwrap();
+    match host.handle_event(&Event::BombPlanted).as_slice() {
+        [Error::ScriptError { path: error_path, hook: Some("on_bomb_planted"), message }] => {
+            assert_eq!(error_path, &path);
+            assert!(message.contains("oh no"), "{}", message);
+        }
+        errors => panic!("expected a hook error, got {:?}", errors),
+    }
+
+    write_script(&path, "let x = ;", 0);
+    assert!(ScriptHost::new().load(&path).is_err());
+}
+
+
+const HELPERS: &str = r#"
+fn on_update(update, previous) {
+    let player = update.player;
+    record("alive " + is_alive(player) + ", team " + team_of(update) + ", holding " + active_weapon(player).name);
+    record("rifle " + player.has_weapon_type("rifle") + ", sniper " + player.has_weapon_type("SniperRifle"));
+    for win in update.map.parsed_round_wins() {
+        record("round " + win.round + ": " + win.team + " by " + win.reason);
+    }
+    if type_of(previous) == "()" {
+        record("first update");
+    } else if previous.player.state.money != player.state.money {
+        record("money changed");
+    }
+    state.updates = if "updates" in state { state.updates + 1 } else { 1 };
+}
+
+fn on_event(event) {
+    record(event.type + " " + event.round + " " + event.winner);
+}
+"#;
+
+#[test]
+fn test_helpers() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("helpers.rhai");
+    write_script(&path, HELPERS, 60);
+    let (mut host, records) = rhai_host_recording();
+    host.load(&path).unwrap();
+
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+    let mut richer = update.clone();
+    richer.player.as_mut().unwrap().state.as_mut().unwrap().money += 300;
+    assert!(host.handle_update(&richer).is_empty());
+    assert!(host.handle_event(&Event::RoundEnded { round: 3, winner: Some(Team::CT) }).is_empty());
+    assert_eq!(*records.borrow(), vec![
+        "alive true, team CT, holding weapon_m4a1_silencer",
+        "rifle true, sniper false",
+        "round 1: T by elimination",
+        "round 2: CT by defuse",
+        "round 3: CT by time",
+        "first update",
+        "alive true, team CT, holding weapon_m4a1_silencer",
+        "rifle true, sniper false",
+        "round 1: T by elimination",
+        "round 2: CT by defuse",
+        "round 3: CT by time",
+        "money changed",
+        "round_ended 3 CT",
+    ]);
+
+    // `state` outlives reloads
+    write_script(&path, "fn on_update(update) { record(\"updates so far: \" + state.updates); }", 0);
+    assert!(host.handle_update(&update).is_empty());
+    assert_eq!(records.borrow().last().unwrap(), "updates so far: 2");
+}
+
+#[test]
+fn test_scripts_over_limits_are_disabled() {
+    let folder = tempfile::tempdir().unwrap();
+    let update = common::captured().update;
+    let limits = ScriptLimits {
+        max_operations: 0,
+        time_budget: Duration::from_millis(50),
+        ..ScriptLimits::default()
+    };
+    let mut host = ScriptHost::with_limits(limits);
+    let spinning = folder.path().join("spinning.rhai");
+    write_script(&spinning, "fn on_update(update) { loop {} }", 60);
+    host.load(&spinning).unwrap();
+    let growing = folder.path().join("growing.rhai");
+    write_script(&growing, "fn on_update(update) { let s = \"x\"; loop { s += s; } }", 60);
+    host.load(&growing).unwrap();
+
+    let started = std::time::Instant::now();
+    let errors = host.handle_update(&update);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    for error in &errors {
+        match error {
+            Error::ScriptError { hook: Some("on_update"), message, .. } => assert!(message.contains("disabled"), "{}", message),
+            error => panic!("expected a hook error, got {:?}", error),
+        }
+    }
+    assert_eq!(host.disabled_scripts().count(), 2);
+    assert!(host.handle_update(&update).is_empty());
+
+    // changing a script gives it another chance
+    write_script(&growing, "fn on_update(update) {}", 0);
+    assert!(host.handle_update(&update).is_empty());
+    assert_eq!(host.disabled_scripts().collect::<Vec<_>>(), vec![spinning.as_path()]);
+}
+
+#[test]
+fn test_scripts_cannot_import() {
+    let folder = tempfile::tempdir().unwrap();
+    write_script(&folder.path().join("secret.rhai"), "export const PASSWORD = \"hunter2\";", 0);
+    let importer = folder.path().join("importer.rhai");
+    write_script(&importer, &format!("import {:?} as secret;", folder.path().join("secret").display().to_string()), 0);
+    match ScriptHost::new().load(&importer) {
+        Err(Error::ScriptError { hook: None, message, .. }) => assert!(message.contains("Module not found"), "{}", message),
+        result => panic!("expected a load error, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_example_script_runs() {
+    let mut host = ScriptHost::new();
+    host.load(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/print_update.rhai")).unwrap();
+    let errors = host.handle_update(&common::captured().update);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
@@ -0,0 +1,389 @@
+use std::fs;
+use std::net::Ipv4Addr;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use futures::StreamExt;
+
+use csgo_gsi::{Error, GSIConfigBuilder, GSIServer, Subscription};
+use csgo_gsi::events::Event;
+use csgo_gsi::replay::{Replayer, Speed};
+use csgo_gsi::simulator::{MatchScript, RoundEnd, Simulator};
+use csgo_gsi::update::Team;
+
+mod common;
+use common::captured;
+
+#[test]
+fn test_advertised_uri_is_installed() {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let config = GSIConfigBuilder::new("uri")
+        .subscribe(Subscription::Provider)
+        .try_build().unwrap();
+    let mut server = GSIServer::new(config, 31343);
+    server.bind_to(Ipv4Addr::UNSPECIFIED);
+    assert_eq!(server.uri(), "http://127.0.0.1:31343");
+    server.advertise_uri("http://192.168.1.2:31343");
+    server.install_into(cfg_folder.path()).unwrap();
+    let installed = fs::read_to_string(cfg_folder.path().join("gamestate_integration_uri.cfg")).unwrap();
+    assert!(installed.contains("\"uri\"\t\"http://192.168.1.2:31343\""), "{}", installed);
+
+    let config = GSIConfigBuilder::new("ipv6").try_build().unwrap();
+    let mut server = GSIServer::new(config, 31343);
+    server.bind_to("::1".parse::<std::net::IpAddr>().unwrap());
+    assert_eq!(server.uri(), "http://[::1]:31343");
+}
+
+#[tokio::test]
+async fn test_non_loopback_requires_auth() {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let config = GSIConfigBuilder::new("insecure").try_build().unwrap();
+    let mut server = GSIServer::new(config, 31344);
+    server.bind_to(Ipv4Addr::UNSPECIFIED);
+    server.install_into(cfg_folder.path()).unwrap();
+    assert!(server.run().await.is_err());
+}
+
+#[tokio::test]
+async fn test_auth_is_verified() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("auth")
+            .subscribe_multiple(Subscription::UNRESTRICTED)
+            .auth("token", "correct horse")
+            .try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.add_listener(move |update| tx.send(update.auth.clone()).unwrap());
+        server
+    });
+
+    let uri = format!("http://{}/", addr);
+    assert!(Replayer::from_captures(vec![captured()]).replay_to(&uri).await.is_err());
+
+    let mut authorized = captured();
+    authorized.raw = authorized.raw.replace("hunter2", "correct horse");
+    Replayer::from_captures(vec![authorized]).replay_to(&uri).await.unwrap();
+    assert_eq!(rx.recv().unwrap()["token"], "correct horse");
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_ephemeral_ports() {
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let config = GSIConfigBuilder::new("ephemeral").try_build().unwrap();
+    let mut server = GSIServer::new(config, 0);
+    assert!(server.local_addr().is_none());
+    assert!(server.install_into(cfg_folder.path()).is_err());
+
+    let addr = server.bind().await.unwrap();
+    assert_ne!(addr.port(), 0);
+    assert_eq!(server.local_addr(), Some(addr));
+    server.install_into(cfg_folder.path()).unwrap();
+    let installed = fs::read_to_string(cfg_folder.path().join("gamestate_integration_ephemeral.cfg")).unwrap();
+    assert!(installed.contains(&format!("http://127.0.0.1:{}", addr.port())), "{}", installed);
+
+    let other = common::spawn_server(|| GSIServer::new(GSIConfigBuilder::new("other").try_build().unwrap(), 0));
+    assert_ne!(other, addr);
+}
+
+#[tokio::test]
+async fn test_service_names_are_escaped() {
+    let (tx, rx) = mpsc::channel();
+    let (updates_tx, updates_rx) = mpsc::channel();
+    let cfg_folder = tempfile::tempdir().unwrap();
+    let cfg_path = cfg_folder.path().to_path_buf();
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let config = GSIConfigBuilder::new("main").try_build().unwrap();
+            let mut server = GSIServer::new(config, 0);
+            let config = GSIConfigBuilder::new("csgo-gsi Example #2").try_build().unwrap();
+            server.add_service(config).unwrap()
+                .add_listener(move |update| updates_tx.send(update.clone()).unwrap());
+            server.bind().await.unwrap();
+            server.install_into(&cfg_path).unwrap();
+            tx.send(()).unwrap();
+            server.run().await.unwrap();
+        });
+    });
+    rx.recv().unwrap();
+
+    let installed = fs::read_to_string(cfg_folder.path().join("gamestate_integration_csgo-gsi Example #2.cfg")).unwrap();
+    let uri = installed.lines()
+        .find_map(|line| line.trim().strip_prefix("\"uri\"\t\""))
+        .and_then(|uri| uri.strip_suffix('"'))
+        .unwrap_or_else(|| panic!("no uri in {}", installed));
+    assert!(uri.ends_with("/svc/csgo-gsi%20Example%20%232"), "{}", uri);
+    Replayer::from_captures(vec![captured()]).replay_to(uri).await.unwrap();
+    assert!(updates_rx.recv().is_ok());
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let (tx, rx) = mpsc::channel();
+    let (updates_tx, updates_rx) = mpsc::channel();
+    let running = std::thread::spawn(move || {
+        let cfg_folder = tempfile::tempdir().unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let config = GSIConfigBuilder::new("shutdown").try_build().unwrap();
+            let mut server = GSIServer::new(config, 0);
+            server.add_listener(move |update| updates_tx.send(update.clone()).unwrap());
+            let addr = server.bind().await.unwrap();
+            server.install_into(cfg_folder.path()).unwrap();
+            tx.send((addr, server.shutdown_handle())).unwrap();
+            server.run().await
+        })
+    });
+    let (addr, shutdown) = rx.recv().unwrap();
+    let uri = format!("http://{}/", addr);
+
+    Replayer::from_captures(vec![captured()]).replay_to(&uri).await.unwrap();
+    shutdown.shutdown();
+    running.join().unwrap().unwrap();
+    assert!(updates_rx.recv().is_ok());
+    assert!(Replayer::from_captures(vec![captured()]).replay_to(&uri).await.is_err());
+}
+
+#[tokio::test]
+async fn test_errors_are_reported() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("errors").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.on_error(move |err| {
+            let report = match err {
+                Error::InvalidJson { raw, .. } => format!("json {}", raw),
+                Error::UpdateSchema { path, value, cause, .. } => format!("schema {} {:?} {}", path, value, cause),
+                other => format!("other {}", other),
+            };
+            tx.send(report).unwrap();
+        });
+        server
+    });
+    let uri = format!("http://{}/", addr);
+
+    let mut not_json = captured();
+    not_json.raw = "not json".to_string();
+    assert!(Replayer::from_captures(vec![not_json]).replay_to(&uri).await.is_err());
+    assert_eq!(rx.recv().unwrap(), "json not json");
+
+    let mut bad_weapon = captured();
+    bad_weapon.raw = bad_weapon.raw.replace("\"Rifle\"", "\"Railgun\"");
+    assert!(Replayer::from_captures(vec![bad_weapon]).replay_to(&uri).await.is_err());
+    let report = rx.recv().unwrap();
+    assert!(report.starts_with("schema /player/weapons/weapon_2/type Some(String(\"Railgun\")) unknown variant `Railgun`, expected one of `Knife`"), "{}", report);
+}
+
+#[tokio::test]
+async fn test_state_endpoints() {
+    let addr = common::spawn_server(|| {
+        let config = GSIConfigBuilder::new("state").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.serve_state(2);
+        server.allow_origin("*");
+        server
+    });
+    let base = format!("http://{}", addr);
+
+    let (response, _) = common::get(&format!("{}/state", base)).await;
+    assert_eq!(response.status(), 404);
+    let (response, body) = common::get(&format!("{}/history", base)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(body, "[]");
+
+    let captures = (0..3u64).map(|round| {
+        let mut captured = captured();
+        captured.raw = captured.raw.replace("\"round\": 3", &format!("\"round\": {}", round));
+        captured
+    }).collect();
+    Replayer::from_captures(captures).replay_to(&format!("{}/", base)).await.unwrap();
+
+    let (response, body) = common::get(&format!("{}/state", base)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    let update: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(update["map"]["round"], 2);
+
+    let (_, body) = common::get(&format!("{}/state/player", base)).await;
+    let player: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(player["name"], "player");
+    let (response, _) = common::get(&format!("{}/state/bogus", base)).await;
+    assert_eq!(response.status(), 404);
+
+    let (_, body) = common::get(&format!("{}/history", base)).await;
+    let history: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(history.iter().map(|entry| entry["id"].as_u64().unwrap()).collect::<Vec<_>>(), vec![2, 3]);
+    let (_, body) = common::get(&format!("{}/history?since=2", base)).await;
+    let history: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["update"]["map"]["round"], 2);
+    let (response, body) = common::get(&format!("{}/history?since=3", base)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(body, "[]");
+}
+
+#[tokio::test]
+async fn test_websocket_topics_and_events() {
+    let builder = GSIConfigBuilder::new("websocket")
+        .subscribe_multiple(Subscription::UNRESTRICTED)
+        .clone();
+    let server_config = builder.try_build().unwrap();
+    let addr = common::spawn_server(move || {
+        let mut server = GSIServer::new(server_config, 0);
+        server.serve_websocket(true);
+        server
+    });
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?topics=round", addr)).await.unwrap();
+
+    let script = MatchScript::new("de_nuke").round(Team::CT, RoundEnd::Defused);
+    let replayer = Simulator::new(&builder.try_build().unwrap(), script).unwrap().replayer().unwrap()
+        .speed(Speed::AsFastAsPossible);
+    replayer.replay_to(&format!("http://{}/", addr)).await.unwrap();
+
+    let mut events = vec![];
+    while !events.contains(&"round_ended".to_string()) {
+        let message = socket.next().await.unwrap().unwrap();
+        let message: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        match message["type"].as_str().unwrap() {
+            "update" => assert_eq!(message["update"].as_object().unwrap().keys().collect::<Vec<_>>(), vec!["round"]),
+            "event" => events.push(message["event"]["type"].as_str().unwrap().to_string()),
+            other => panic!("unexpected message type {}", other),
+        }
+    }
+    assert_eq!(events, vec!["round_started", "bomb_planted", "bomb_defused", "round_ended"]);
+}
+
+#[tokio::test]
+async fn test_sse_resumes_from_last_event_id() {
+    let addr = common::spawn_server(|| {
+        let config = GSIConfigBuilder::new("sse").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.serve_sse(16);
+        server
+    });
+    let uri = format!("http://{}/", addr);
+    Replayer::from_captures(vec![captured(), captured()]).replay_to(&uri).await.unwrap();
+
+    let request = gotham::hyper::Request::get(format!("http://{}/events", addr))
+        .header("Last-Event-ID", "6")
+        .body(gotham::hyper::Body::empty())
+        .unwrap();
+    let response = gotham::hyper::Client::new().request(request).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body();
+
+    let mut received = String::new();
+    let mut events = vec![];
+    let mut replayed = false;
+    while events.len() < 6 {
+        if events.len() == 2 && !replayed {
+            Replayer::from_captures(vec![captured()]).replay_to(&uri).await.unwrap();
+            replayed = true;
+        }
+        let chunk = body.next().await.unwrap().unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = received.find("\n\n") {
+            let message = received[..end].to_string();
+            received.drain(..end + 2);
+            let fields = message.lines()
+                .filter(|line| !line.starts_with(':'))
+                .filter_map(|line| line.split_once(": "))
+                .collect::<std::collections::HashMap<_, _>>();
+            if let (Some(id), Some(event)) = (fields.get("id"), fields.get("event")) {
+                events.push(format!("{} {}", id, event));
+            }
+        }
+    }
+    assert_eq!(events, vec!["7 provider", "8 round", "9 map", "10 player", "11 provider", "12 round"]);
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("metrics").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.serve_metrics();
+        server.add_listener(move |_| tx.send(()).unwrap());
+        server
+    });
+    let uri = format!("http://{}/", addr);
+    Replayer::from_captures(vec![captured(), captured()]).replay_to(&uri).await.unwrap();
+    rx.recv().unwrap();
+    rx.recv().unwrap();
+    let mut not_json = captured();
+    not_json.raw = "not json".to_string();
+    assert!(Replayer::from_captures(vec![not_json]).replay_to(&uri).await.is_err());
+
+    let (response, body) = common::get(&format!("http://{}/metrics", addr)).await;
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let lines = body.lines().collect::<Vec<_>>();
+    assert!(lines.contains(&"csgo_gsi_updates_received_total 2"), "{}", body);
+    assert!(lines.contains(&"csgo_gsi_updates_rejected_total{reason=\"invalid_json\"} 1"), "{}", body);
+    assert!(lines.contains(&"csgo_gsi_updates_rejected_total{reason=\"schema\"} 0"), "{}", body);
+    assert!(lines.contains(&"csgo_gsi_parse_duration_seconds_count 3"), "{}", body);
+    assert!(lines.contains(&"csgo_gsi_round 3"), "{}", body);
+    assert!(lines.iter().any(|line| line.starts_with("csgo_gsi_seconds_since_last_update ")), "{}", body);
+}
+
+#[tokio::test]
+async fn test_heartbeat_watchdog() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("watchdog")
+            .throttle(Duration::from_millis(100))
+            .heartbeat(Duration::from_millis(100))
+            .try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.heartbeat_grace(Duration::from_millis(100));
+        server.add_event_listener(move |event| {
+            if event.topic() == "provider" {
+                tx.send(event.clone()).unwrap();
+            }
+        });
+        server
+    });
+    let uri = format!("http://{}/", addr);
+
+    Replayer::from_captures(vec![captured()]).replay_to(&uri).await.unwrap();
+    assert_eq!(rx.recv().unwrap(), Event::GameConnected);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), Event::GameDisconnected);
+
+    let mut restarted = captured();
+    restarted.raw = restarted.raw.replace("\"timestamp\": 1600000000", "\"timestamp\": 1500000000");
+    Replayer::from_captures(vec![restarted]).replay_to(&uri).await.unwrap();
+    assert_eq!(rx.recv().unwrap(), Event::GameConnected);
+    assert_eq!(rx.recv().unwrap(), Event::GameRestarted);
+}
+
+#[tokio::test]
+async fn test_services_get_their_own_updates() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("main").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        let main = tx.clone();
+        server.add_listener(move |_| main.send("main").unwrap());
+        let config = GSIConfigBuilder::new("coach")
+            .auth("token", "correct horse")
+            .try_build().unwrap();
+        server.add_service(config).unwrap()
+            .add_listener(move |_| tx.send("coach").unwrap());
+        let config = GSIConfigBuilder::new("coach").try_build().unwrap();
+        assert!(server.add_service(config).is_err());
+        server
+    });
+
+    let mut authorized = captured();
+    authorized.raw = authorized.raw.replace("hunter2", "correct horse");
+    let uri = format!("http://{}/svc/coach", addr);
+    assert!(Replayer::from_captures(vec![captured()]).replay_to(&uri).await.is_err());
+    Replayer::from_captures(vec![authorized]).replay_to(&uri).await.unwrap();
+    assert_eq!(rx.recv().unwrap(), "coach");
+
+    let uri = format!("http://{}/svc/nobody", addr);
+    assert!(Replayer::from_captures(vec![captured()]).replay_to(&uri).await.is_err());
+    Replayer::from_captures(vec![captured()]).replay_to(&format!("http://{}/", addr)).await.unwrap();
+    assert_eq!(rx.recv().unwrap(), "main");
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use csgo_gsi::{GSIConfigBuilder, Subscription};
+use csgo_gsi::simulator::{MatchScript, RoundEnd, Simulator};
+use csgo_gsi::update::{Team, map, round};
+
+fn config() -> csgo_gsi::GSIConfig {
+    GSIConfigBuilder::new("csgo-gsi simulator test")
+        .subscribe_multiple(Subscription::UNRESTRICTED)
+        .auth("token", "hunter2")
+        .try_build().unwrap()
+}
+
+#[test]
+fn test_pistol_round_then_plant() {
+    let script = MatchScript::new("de_dust2")
+        .round(Team::CT, RoundEnd::Elimination)
+        .round(Team::T, RoundEnd::BombExploded);
+    let captures = Simulator::new(&config(), script).unwrap().captures().unwrap();
+
+    let last = &captures.last().unwrap().update;
+    let map = last.map.as_ref().unwrap();
+    assert_eq!(map.round, 2);
+    assert_eq!(map.round_wins[&1], "ct_win_elimination");
+    assert_eq!(map.round_wins[&2], "t_win_bomb");
+    assert_eq!(map.team_ct.score, 1);
+    assert_eq!(map.team_t.score, 1);
+    assert_eq!(last.auth["token"], "hunter2");
+
+    assert!(captures.iter().any(|captured| matches!(captured.update.round.as_ref().unwrap().bomb, Some(round::BombState::Planted))));
+    let planted = captures.iter()
+        .find(|captured| matches!(captured.update.round.as_ref().unwrap().bomb, Some(round::BombState::Planted)))
+        .unwrap();
+    let raw: serde_json::Value = serde_json::from_str(&planted.raw).unwrap();
+    assert_eq!(raw["previously"]["round"]["bomb"], serde_json::Value::Null);
+    assert_eq!(raw["added"]["round"]["bomb"], true);
+}
+
+#[test]
+fn test_timing_follows_config() {
+    let config = GSIConfigBuilder::new("csgo-gsi simulator test")
+        .subscribe(Subscription::Provider)
+        .throttle(Duration::from_secs(5))
+        .try_build().unwrap();
+    let script = MatchScript::new("de_dust2").round(Team::CT, RoundEnd::TimeRanOut);
+    let captures = Simulator::new(&config, script).unwrap().captures().unwrap();
+    for pair in captures.windows(2) {
+        let gap = pair[1].received_at - pair[0].received_at;
+        assert!(gap >= 5_000, "updates only {}ms apart", gap);
+        assert!(gap <= 60_000, "updates {}ms apart", gap);
+    }
+}
+
+#[test]
+fn test_overtime_and_surrender() {
+    let script = MatchScript::new("de_inferno")
+        .rounds(15, Team::CT, RoundEnd::Elimination)
+        .rounds(15, Team::CT, RoundEnd::Elimination)
+        .rounds(3, Team::T, RoundEnd::Elimination)
+        .rounds(2, Team::CT, RoundEnd::Elimination);
+    let captures = Simulator::new(&config(), script).unwrap().captures().unwrap();
+    let map = captures.last().unwrap().update.map.clone().unwrap();
+    assert_eq!(map.round, 34);
+    assert_eq!(map.phase, map::Phase::GameOver);
+    assert!(captures.iter().any(|captured| captured.update.map.as_ref().unwrap().phase == map::Phase::Intermission));
+
+    let script = MatchScript::new("de_nuke")
+        .round(Team::T, RoundEnd::Elimination)
+        .round(Team::CT, RoundEnd::Surrender)
+        .round(Team::T, RoundEnd::Elimination);
+    let captures = Simulator::new(&config(), script).unwrap().captures().unwrap();
+    let map = captures.last().unwrap().update.map.clone().unwrap();
+    assert_eq!(map.round, 2);
+    assert_eq!(map.phase, map::Phase::GameOver);
+    assert_eq!(map.round_wins[&2], "ct_win_surrender");
+}
+
+#[test]
+fn test_impossible_round_end() {
+    let script = MatchScript::new("de_dust2").round(Team::T, RoundEnd::Defused);
+    assert!(Simulator::new(&config(), script).is_err());
+}
@@ -0,0 +1,73 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+use csgo_gsi::{GSIConfigBuilder, GSIServer, Subscription, Update};
+use csgo_gsi::replay::Replayer;
+use csgo_gsi::team::{ClientId, TeamView};
+use csgo_gsi::update::Team;
+
+mod common;
+use common::{captured, UPDATE};
+
+/// the fixture, as sent by the given player's game
+fn update_from(steam_id: &str, name: &str, money: u64) -> Update {
+    let mut update: Update = serde_json::from_str(UPDATE).unwrap();
+    update.provider.as_mut().unwrap().steam_id = steam_id.to_string();
+    let player = update.player.as_mut().unwrap();
+    player.steam_id = steam_id.to_string();
+    player.name = name.to_string();
+    player.state.as_mut().unwrap().money = money;
+    update
+}
+
+#[test]
+fn test_team_view_merges_clients() {
+    let mut view = TeamView::new();
+    for (steam_id, name, money) in &[("1", "alice", 800), ("2", "bob", 1200), ("3", "carol", 4750)] {
+        let update = update_from(steam_id, name, *money);
+        view.update(&ClientId::of(&update).unwrap(), &update);
+    }
+    assert_eq!(view.members().len(), 3);
+    assert_eq!(view.total_money(Team::CT), 6750);
+    assert_eq!(view.alive(Team::CT), 3);
+    assert_eq!(view.team(Team::T).count(), 0);
+    assert_eq!(view.map().unwrap().name, "de_dust2");
+
+    // bob dies and starts spectating alice, whose info shouldn't overwrite his
+    let mut update = update_from("2", "bob", 1200);
+    update.player.as_mut().unwrap().state.as_mut().unwrap().health = 0;
+    let bob = ClientId::of(&update).unwrap();
+    view.update(&bob, &update);
+    let mut spectating = update_from("1", "alice", 800);
+    spectating.provider.as_mut().unwrap().steam_id = "2".to_string();
+    view.update(&ClientId::of(&spectating).unwrap(), &spectating);
+    let member = &view.members()[&bob];
+    assert_eq!(member.name, "bob");
+    assert!(!member.is_alive());
+    assert_eq!(member.weapons.len(), 3);
+    assert_eq!(view.alive(Team::CT), 2);
+    assert_eq!(view.members().len(), 3);
+}
+
+#[tokio::test]
+async fn test_client_listener() {
+    let (tx, rx) = mpsc::channel();
+    let view = Arc::new(Mutex::new(TeamView::new()));
+    let server_view = view.clone();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::new("team").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        assert!(server.config().subscriptions().is_empty());
+        server.add_client_listener(move |client, update| {
+            server_view.lock().unwrap().update(client, update);
+            tx.send(client.clone()).unwrap();
+        });
+        assert!(server.config().subscriptions().contains(&Subscription::Provider));
+        server
+    });
+
+    Replayer::from_captures(vec![captured()]).replay_to(&format!("http://{}/", addr)).await.unwrap();
+    let client = rx.recv().unwrap();
+    assert_eq!(client.steam_id, "76561198000000000");
+    assert_eq!(client.auth["token"], "hunter2");
+    assert_eq!(view.lock().unwrap().members()[&client].money, 3150);
+}
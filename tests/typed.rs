@@ -0,0 +1,66 @@
+use std::sync::mpsc;
+
+use csgo_gsi::{Error, GSIConfigBuilder, GSIServer, Subscription, TypedUpdate};
+use csgo_gsi::replay::Replayer;
+use csgo_gsi::update::typed::{Absent, MapSub, PlayerStateSub, RoundSub};
+
+mod common;
+use common::{captured, UPDATE};
+
+type Subs = (MapSub, PlayerStateSub, RoundSub);
+
+#[test]
+fn test_typed_config_subscribes() {
+    let config = GSIConfigBuilder::<Subs>::typed("typed")
+        .subscribe(Subscription::Provider)
+        .try_build()
+        .unwrap();
+    let mut subscriptions = config.subscriptions().iter().copied().collect::<Vec<_>>();
+    subscriptions.sort_by_key(|subscription| format!("{:?}", subscription));
+    assert_eq!(subscriptions, vec![Subscription::Map, Subscription::PlayerState, Subscription::Provider, Subscription::Round]);
+}
+
+#[test]
+fn test_typed_sections() {
+    let update = TypedUpdate::<Subs>::from_json(UPDATE).unwrap();
+    assert_eq!(update.map.round, 3);
+    assert_eq!(update.player.state.health, 100);
+    assert_eq!(update.player.match_stats, Absent);
+    assert_eq!(update.provider, Absent);
+    assert_eq!(update.round.win_team, None);
+
+    let mut raw: serde_json::Value = serde_json::from_str(UPDATE).unwrap();
+    raw["player"].as_object_mut().unwrap().remove("state");
+    match TypedUpdate::<Subs>::from_json(&raw.to_string()) {
+        Err(Error::UpdateSchema { path, cause, .. }) => {
+            assert_eq!(path, "/player");
+            assert!(cause.to_string().contains("missing field `state`"), "{}", cause);
+        }
+        other => panic!("expected a schema error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_typed_listener() {
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let config = GSIConfigBuilder::<Subs>::typed("typed").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        let updates = tx.clone();
+        server.add_typed_listener(move |update| updates.send(Ok(update.player.state.money)).unwrap());
+        server.on_error(move |err| tx.send(Err(err.to_string())).unwrap());
+        server
+    });
+    let uri = format!("http://{}/", addr);
+
+    Replayer::from_captures(vec![captured()]).replay_to(&uri).await.unwrap();
+    assert_eq!(rx.recv().unwrap(), Ok(3150));
+
+    let mut no_round = captured();
+    let mut raw: serde_json::Value = serde_json::from_str(&no_round.raw).unwrap();
+    raw.as_object_mut().unwrap().remove("round");
+    no_round.raw = raw.to_string();
+    Replayer::from_captures(vec![no_round]).replay_to(&uri).await.unwrap();
+    let error = rx.recv().unwrap().unwrap_err();
+    assert!(error.contains("missing field `round`"), "{}", error);
+}
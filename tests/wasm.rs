@@ -0,0 +1,168 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use csgo_gsi::{Error, GSIConfigBuilder, GSIServer};
+use csgo_gsi::events::Event;
+use csgo_gsi::replay::Replayer;
+use csgo_gsi::scripting::{PluginEvent, WasmLimits, WasmPluginHost};
+
+mod common;
+use common::write_script;
+
+/// echoes updates and events back as emitted events, and sets a timer on each update
+const ECHO: &str = r#"
+(module
+  (import "csgo_gsi" "emit" (func $emit (param i32 i32 i32 i32)))
+  (import "csgo_gsi" "set_timer" (func $set_timer (param i32 i32)))
+  (import "csgo_gsi" "log" (func $log (param i32 i32 i32)))
+  (memory (export "memory") 4)
+  (data (i32.const 0) "update")
+  (data (i32.const 8) "timer")
+  (data (i32.const 16) "{}")
+  (data (i32.const 24) "event")
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 1024))
+  (func (export "on_update") (param $ptr i32) (param $len i32)
+    (call $log (i32.const 2) (i32.const 0) (i32.const 6))
+    (call $emit (i32.const 0) (i32.const 6) (local.get $ptr) (local.get $len))
+    (call $set_timer (i32.const 7) (i32.const 0)))
+  (func (export "on_event") (param $ptr i32) (param $len i32)
+    (call $emit (i32.const 24) (i32.const 5) (local.get $ptr) (local.get $len)))
+  (func (export "on_timer") (param $id i32)
+    (call $emit (i32.const 8) (i32.const 5) (i32.const 16) (i32.const 2)))
+)
+"#;
+
+const SPINNING: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 0))
+  (func (export "on_update") (param $ptr i32) (param $len i32)
+    (loop $forever (br $forever)))
+)
+"#;
+
+#[test]
+fn test_plugins_get_updates_events_and_timers() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("echo.wat");
+    write_script(&path, ECHO, 0);
+    let mut host = WasmPluginHost::new();
+    let emitted = Rc::new(RefCell::new(vec![]));
+    let recorder = emitted.clone();
+    host.add_event_listener(move |event: &PluginEvent| recorder.borrow_mut().push((event.name.clone(), event.data.clone())));
+    host.load(&path).unwrap();
+
+    let update = common::captured().update;
+    assert!(host.handle_update(&update).is_empty());
+    // even a timer set for 0 milliseconds takes at least 10 to go off
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(host.handle_event(&Event::BombPlanted).is_empty());
+    assert_eq!(*emitted.borrow(), vec![
+        ("update".to_string(), serde_json::to_value(&update).unwrap()),
+        ("timer".to_string(), json!({})),
+        ("event".to_string(), json!({"type": "bomb_planted"})),
+    ]);
+
+    // timers only go off once
+    assert!(host.handle_event(&Event::BombPlanted).is_empty());
+    assert_eq!(emitted.borrow().len(), 4);
+}
+
+#[test]
+fn test_timers_cant_be_rearmed_immediately() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("rearm.wat");
+    let rearm = ECHO.replace(
+        "(call $emit (i32.const 8) (i32.const 5) (i32.const 16) (i32.const 2)))",
+        "(call $emit (i32.const 8) (i32.const 5) (i32.const 16) (i32.const 2))\n    (call $set_timer (local.get $id) (i32.const 0)))",
+    );
+    write_script(&path, &rearm, 0);
+    let mut host = WasmPluginHost::new();
+    let timers = Rc::new(RefCell::new(0));
+    let counter = timers.clone();
+    host.add_event_listener(move |event: &PluginEvent| if event.name == "timer" { *counter.borrow_mut() += 1 });
+    host.load(&path).unwrap();
+
+    assert!(host.handle_update(&common::captured().update).is_empty());
+    assert!(host.next_timer().unwrap() >= Instant::now() + Duration::from_millis(5));
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_millis(100) {
+        assert!(host.fire_timers().is_empty());
+    }
+    // going off every time fire_timers was called would be thousands of times
+    assert!(*timers.borrow() <= 10, "timer went off {} times", timers.borrow());
+}
+
+#[tokio::test]
+async fn test_servers_fire_timers_between_updates() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("echo.wat");
+    write_script(&path, &ECHO.replace("(call $set_timer (i32.const 7) (i32.const 0))", "(call $set_timer (i32.const 7) (i32.const 200))"), 0);
+    let (tx, rx) = mpsc::channel();
+    let addr = common::spawn_server(move || {
+        let mut host = WasmPluginHost::new();
+        host.add_event_listener(move |event: &PluginEvent| tx.send(event.name.clone()).unwrap());
+        host.load(&path).unwrap();
+        let config = GSIConfigBuilder::new("timers").try_build().unwrap();
+        let mut server = GSIServer::new(config, 0);
+        server.add_script_host(host);
+        server
+    });
+
+    Replayer::from_captures(vec![common::captured()]).replay_to(&format!("http://{}/", addr)).await.unwrap();
+    // no more updates or events are coming, so only the server can set the timer off
+    loop {
+        match rx.recv_timeout(Duration::from_secs(5)) {
+            Ok(name) if name == "timer" => break,
+            Ok(_) => continue,
+            Err(err) => panic!("timer never went off: {}", err),
+        }
+    }
+}
+
+#[test]
+fn test_plugins_are_sandboxed() {
+    let folder = tempfile::tempdir().unwrap();
+    let update = common::captured().update;
+    let mut host = WasmPluginHost::with_limits(WasmLimits {
+        fuel: 100_000,
+        ..WasmLimits::default()
+    });
+    let spinning = folder.path().join("spinning.wat");
+    write_script(&spinning, SPINNING, 60);
+    host.load(&spinning).unwrap();
+    let trapping = folder.path().join("trapping.wat");
+    write_script(&trapping, &SPINNING.replace("(loop $forever (br $forever))", "unreachable"), 60);
+    host.load(&trapping).unwrap();
+
+    let errors = host.handle_update(&update);
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    match errors.as_slice() {
+        [Error::ScriptError { hook: Some("on_update"), message: spinning, .. }, Error::ScriptError { hook: Some("on_update"), message: trapping, .. }] => {
+            assert!(spinning.contains("disabled"), "{}", spinning);
+            assert!(!trapping.contains("disabled"), "{}", trapping);
+        }
+        errors => panic!("expected hook errors, got {:?}", errors),
+    }
+    assert_eq!(host.disabled_plugins().collect::<Vec<_>>(), vec![spinning.as_path()]);
+    assert_eq!(host.handle_update(&update).len(), 1);
+
+    // changing a plugin gives it another chance
+    write_script(&spinning, &SPINNING.replace("(loop $forever (br $forever))", ""), 0);
+    assert_eq!(host.handle_update(&update).len(), 1);
+    assert_eq!(host.disabled_plugins().count(), 0);
+
+    // only the host API can be imported
+    let escaping = folder.path().join("escaping.wat");
+    write_script(&escaping, r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))))"#, 0);
+    match host.load(&escaping) {
+        Err(Error::ScriptError { hook: None, .. }) => {}
+        result => panic!("expected a load error, got {:?}", result),
+    }
+}